@@ -0,0 +1,420 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+use image::codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder};
+use image::io::Reader as ImageReader;
+use image::{AnimationDecoder, Delay, DynamicImage, Frames, ImageFormat};
+
+use crate::error::ResizedPngError;
+
+/// 分けて出力するときに、ファイル名のフレームの番号に置き換える文字列
+pub(crate) const FRAME_NUMBER_PATTERN: &str = "{}";
+
+/// アニメーションの扱い
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub(crate) enum AnimationMode {
+    /// 最初のフレームだけを出力する
+    #[default]
+    None,
+    /// すべてのフレームをAPNGとして出力する
+    Apng,
+    /// すべてのフレームを番号付きのファイルに分けて出力する
+    Split,
+}
+
+impl AnimationMode {
+    pub(crate) fn from_name(name: &str) -> Option<AnimationMode> {
+        match name {
+            "None" => Some(AnimationMode::None),
+            "Apng" => Some(AnimationMode::Apng),
+            "Split" => Some(AnimationMode::Split),
+            _ => None,
+        }
+    }
+}
+
+/// アニメーションする画像のすべてのフレームを読み込む関数。
+/// 対応していない形式や、アニメーションしない画像の場合は`None`を返す。
+pub(crate) fn read_frames(
+    src_path: &Path,
+) -> Result<Option<Vec<(DynamicImage, Delay)>>, ResizedPngError> {
+    let Some(frames) = decode_frames(src_path)? else {
+        return Ok(None);
+    };
+
+    // フレームはすべて画像全体の大きさに合成されている。
+    let frames = frames
+        .collect_frames()?
+        .into_iter()
+        .map(|v| {
+            let delay = v.delay();
+            (DynamicImage::ImageRgba8(v.into_buffer()), delay)
+        })
+        .collect();
+
+    Ok(Some(frames))
+}
+
+/// 画像のフレームの数を返す関数。
+/// 画素は読み込まず、gifはブロック、APNGは`acTL`、WebPはチャンクから数える。
+/// アニメーションしない画像は1を返す。
+pub(crate) fn frame_count(src_path: &Path) -> Result<usize, ResizedPngError> {
+    let reader = ImageReader::open(src_path)?.with_guessed_format()?;
+
+    match reader.format() {
+        Some(ImageFormat::Gif) => count_gif_frames(&mut BufReader::new(File::open(src_path)?)),
+        Some(ImageFormat::Png) => {
+            let decoder = png::Decoder::new(BufReader::new(File::open(src_path)?));
+            let reader = decoder.read_info()?;
+            Ok(reader
+                .info()
+                .animation_control
+                .map_or(1, |v| v.num_frames as usize))
+        }
+        Some(ImageFormat::WebP) => count_webp_frames(&mut BufReader::new(File::open(src_path)?)),
+        _ => {
+            // アニメーションしない画像も、読み込めることを確かめる。
+            reader.into_dimensions()?;
+            Ok(1)
+        }
+    }
+}
+
+/// gifのブロックを順に読み、画像のブロックの数を数える関数。
+/// 画像のデータは展開せずに読み飛ばす。
+fn count_gif_frames<R: Read>(reader: &mut R) -> Result<usize, ResizedPngError> {
+    // ヘッダと論理画面記述子
+    let mut header = [0; 13];
+    reader.read_exact(&mut header)?;
+    skip_gif_color_table(reader, header[10])?;
+
+    let mut count = 0;
+    let mut block_type = [0; 1];
+    // 終端のブロックがないファイルもあるため、ファイルの終わりでも終了する。
+    while read_next(reader, &mut block_type)? {
+        match block_type[0] {
+            // 拡張ブロック
+            0x21 => {
+                let mut label = [0; 1];
+                reader.read_exact(&mut label)?;
+                skip_gif_sub_blocks(reader)?;
+            }
+            // 画像ブロック
+            0x2C => {
+                let mut descriptor = [0; 9];
+                reader.read_exact(&mut descriptor)?;
+                skip_gif_color_table(reader, descriptor[8])?;
+                // LZWの最小コードサイズ
+                let mut code_size = [0; 1];
+                reader.read_exact(&mut code_size)?;
+                skip_gif_sub_blocks(reader)?;
+                count += 1;
+            }
+            // 終端
+            0x3B => break,
+            _ => return Err(ResizedPngError::DecodingError),
+        }
+    }
+
+    Ok(count)
+}
+
+/// gifのフラグにカラーテーブルがあれば、読み飛ばす関数。
+fn skip_gif_color_table<R: Read>(reader: &mut R, flags: u8) -> Result<(), ResizedPngError> {
+    if flags & 0x80 == 0 {
+        return Ok(());
+    }
+
+    skip_bytes(reader, 3 << ((flags & 0x07) + 1))
+}
+
+/// gifのサブブロックを、長さ0のブロックまで読み飛ばす関数。
+fn skip_gif_sub_blocks<R: Read>(reader: &mut R) -> Result<(), ResizedPngError> {
+    let mut len = [0; 1];
+    loop {
+        reader.read_exact(&mut len)?;
+        if len[0] == 0 {
+            return Ok(());
+        }
+        skip_bytes(reader, len[0] as u64)?;
+    }
+}
+
+/// WebPのチャンクを順に読み、`ANMF`チャンクの数を数える関数。
+/// `ANMF`チャンクがない場合は、アニメーションしない画像として1を返す。
+fn count_webp_frames<R: Read>(reader: &mut R) -> Result<usize, ResizedPngError> {
+    // `RIFF`、ファイルの大きさ、`WEBP`
+    let mut header = [0; 12];
+    reader.read_exact(&mut header)?;
+
+    let mut count = 0;
+    let mut chunk_header = [0; 8];
+    while read_next(reader, &mut chunk_header)? {
+        let (fourcc, len) = chunk_header.split_at(4);
+        let len = u32::from_le_bytes(len.try_into().expect("length is 4 bytes")) as u64;
+        if fourcc == b"ANMF" {
+            count += 1;
+        }
+        // チャンクの大きさは偶数に揃えられている。
+        skip_bytes(reader, len + len % 2)?;
+    }
+
+    Ok(count.max(1))
+}
+
+/// データが残っていれば、`buf`の大きさだけ読み込む関数。
+/// ファイルの終わりに達していた場合は`false`を返す。
+fn read_next<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<bool, ResizedPngError> {
+    if reader.read(&mut buf[..1])? == 0 {
+        return Ok(false);
+    }
+    reader.read_exact(&mut buf[1..])?;
+
+    Ok(true)
+}
+
+/// 指定された長さだけ読み飛ばす関数。
+fn skip_bytes<R: Read>(reader: &mut R, len: u64) -> Result<(), ResizedPngError> {
+    match std::io::copy(&mut reader.by_ref().take(len), &mut std::io::sink())? == len {
+        true => Ok(()),
+        false => Err(ResizedPngError::DecodingError),
+    }
+}
+
+/// アニメーションする画像のフレームを読み込むものを返す関数。
+fn decode_frames(src_path: &Path) -> Result<Option<Frames<'static>>, ResizedPngError> {
+    let format = ImageReader::open(src_path)
+        .and_then(|v| v.with_guessed_format())?
+        .format();
+    let reader = BufReader::new(File::open(src_path)?);
+
+    let frames = match format {
+        Some(ImageFormat::Gif) => GifDecoder::new(reader)?.into_frames(),
+        Some(ImageFormat::Png) => {
+            let decoder = PngDecoder::new(reader)?;
+            if !decoder.is_apng() {
+                return Ok(None);
+            }
+            decoder.apng().into_frames()
+        }
+        Some(ImageFormat::WebP) => {
+            let decoder = WebPDecoder::new(reader)?;
+            if !decoder.has_animation() {
+                return Ok(None);
+            }
+            decoder.into_frames()
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(frames))
+}
+
+/// フレームの表示時間をミリ秒で返す関数。
+pub(crate) fn delay_to_millis(delay: Delay) -> u16 {
+    let (numerator, denominator) = delay.numer_denom_ms();
+    let millis = (numerator as u64 + denominator as u64 / 2) / denominator as u64;
+
+    millis.min(u16::MAX as u64) as u16
+}
+
+/// 出力するファイル名の`{}`をフレームの番号に置き換える関数。
+/// `{}`がない場合は`None`を返す。
+pub(crate) fn numbered_path(dist_path: &Path, index: usize) -> Option<PathBuf> {
+    let path = dist_path.to_string_lossy();
+
+    if !path.contains(FRAME_NUMBER_PATTERN) {
+        return None;
+    }
+
+    Some(PathBuf::from(
+        path.replace(FRAME_NUMBER_PATTERN, &index.to_string()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::codecs::gif::GifEncoder;
+    use image::{Frame, Rgba, RgbaImage};
+    use tempfile::tempdir;
+
+    /// 色の異なる3つのフレームを持つgifを作る。
+    fn prepare_animated_gif(path: &Path) {
+        let mut encoder = GifEncoder::new(File::create(path).unwrap());
+        for (i, color) in [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]]
+            .iter()
+            .enumerate()
+        {
+            let buffer = RgbaImage::from_pixel(8, 6, Rgba(*color));
+            let delay = Delay::from_numer_denom_ms(100 * (i as u32 + 1), 1);
+            encoder
+                .encode_frame(Frame::from_parts(buffer, 0, 0, delay))
+                .unwrap();
+        }
+    }
+
+    mod animation_mode {
+        use super::*;
+
+        #[test]
+        fn checking_value_of_each_name() {
+            assert_eq!(AnimationMode::from_name("None"), Some(AnimationMode::None));
+            assert_eq!(AnimationMode::from_name("Apng"), Some(AnimationMode::Apng));
+            assert_eq!(
+                AnimationMode::from_name("Split"),
+                Some(AnimationMode::Split)
+            );
+            assert_eq!(AnimationMode::from_name("Gif"), None);
+        }
+    }
+
+    mod read_frames {
+        use super::*;
+
+        #[test]
+        fn all_frames_when_image_is_animated_gif() {
+            let out_dir = tempdir().unwrap();
+            let path = out_dir.path().join("animated.gif");
+            prepare_animated_gif(&path);
+
+            let frames = read_frames(&path).unwrap().unwrap();
+
+            assert_eq!(frames.len(), 3);
+            for (i, (image, delay)) in frames.iter().enumerate() {
+                assert_eq!((image.width(), image.height()), (8, 6));
+                assert_eq!(delay_to_millis(*delay), 100 * (i as u16 + 1));
+            }
+            assert_eq!(frames[1].0.to_rgba8().get_pixel(0, 0).0, [0, 255, 0, 255]);
+        }
+
+        #[test]
+        fn none_when_image_is_not_animated() {
+            let path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.png");
+
+            assert!(read_frames(&path).unwrap().is_none());
+        }
+    }
+
+    mod frame_count {
+        use super::*;
+
+        #[test]
+        fn number_of_frames_when_image_is_animated_gif() {
+            let out_dir = tempdir().unwrap();
+            let path = out_dir.path().join("animated.gif");
+            prepare_animated_gif(&path);
+
+            assert_eq!(frame_count(&path).unwrap(), 3);
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn number_of_frames_when_image_is_apng() {
+            let out_dir = tempdir().unwrap();
+            let path = out_dir.path().join("animated.png");
+            let mut encoder = png::Encoder::new(File::create(&path).unwrap(), 2, 2);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_animated(4, 0).unwrap();
+            let mut writer = encoder.write_header().unwrap();
+            for _ in 0..4 {
+                writer.write_image_data(&[0; 2 * 2 * 4]).unwrap();
+            }
+            writer.finish().unwrap();
+
+            assert_eq!(frame_count(&path).unwrap(), 4);
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn number_of_chunks_without_decoding_when_image_is_animated_webp() {
+            let out_dir = tempdir().unwrap();
+            let path = out_dir.path().join("animated.webp");
+            // フレームの中身は読まないので、空のデータにしておく。
+            let mut chunks = Vec::new();
+            chunks.extend_from_slice(b"VP8X");
+            chunks.extend_from_slice(&10u32.to_le_bytes());
+            chunks.extend_from_slice(&[0x02, 0, 0, 0, 1, 0, 0, 1, 0, 0]);
+            for _ in 0..2 {
+                chunks.extend_from_slice(b"ANMF");
+                chunks.extend_from_slice(&3u32.to_le_bytes());
+                chunks.extend_from_slice(&[0, 0, 0, 0]);
+            }
+            let mut data = Vec::new();
+            data.extend_from_slice(b"RIFF");
+            data.extend_from_slice(&(chunks.len() as u32 + 4).to_le_bytes());
+            data.extend_from_slice(b"WEBP");
+            data.extend_from_slice(&chunks);
+            std::fs::write(&path, data).unwrap();
+
+            assert_eq!(frame_count(&path).unwrap(), 2);
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn one_when_image_is_not_animated_gif_png_or_webp() {
+            for name in ["sample.gif", "sample.png", "sample.webp"] {
+                let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                    .join("test_target/image")
+                    .join(name);
+
+                assert_eq!(frame_count(&path).unwrap(), 1, "{}", name);
+            }
+        }
+
+        #[test]
+        fn one_when_image_is_not_animated() {
+            let path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.bmp");
+
+            assert_eq!(frame_count(&path).unwrap(), 1);
+        }
+
+        #[test]
+        fn failed_when_file_does_not_exist() {
+            let path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/not_exist.png");
+
+            assert!(matches!(frame_count(&path), Err(ResizedPngError::NotFound)));
+        }
+    }
+
+    mod delay_to_millis {
+        use super::*;
+
+        #[test]
+        fn rounded_millis_when_delay_is_fraction() {
+            assert_eq!(delay_to_millis(Delay::from_numer_denom_ms(100, 1)), 100);
+            assert_eq!(delay_to_millis(Delay::from_numer_denom_ms(200, 3)), 67);
+            assert_eq!(
+                delay_to_millis(Delay::from_numer_denom_ms(100_000, 1)),
+                u16::MAX
+            );
+        }
+    }
+
+    mod numbered_path {
+        use super::*;
+
+        #[test]
+        fn replaced_path_when_pattern_is_included() {
+            assert_eq!(
+                numbered_path(Path::new("out/frame_{}.png"), 12),
+                Some(PathBuf::from("out/frame_12.png"))
+            );
+        }
+
+        #[test]
+        fn none_when_pattern_is_not_included() {
+            assert_eq!(numbered_path(Path::new("out/frame.png"), 0), None);
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use std::{num::NonZeroU32, path::PathBuf};
+
+use image::DynamicImage;
+
+use crate::error::ResizedPngError;
+use crate::options::ResizeOptions;
+use crate::resized_png::{read_prepared_frames, Rect};
+
+/// 透明度がしきい値を超える画素を囲む矩形範囲を返す関数。
+/// そのような画素がない場合は`None`を返す。透明度を持たない画像は全体を返す。
+pub(crate) fn opaque_bounds(image: &DynamicImage, threshold: u8) -> Option<Rect> {
+    let (width, height) = (image.width(), image.height());
+
+    if !image.color().has_alpha() {
+        return Some(Rect {
+            left: 0,
+            top: 0,
+            width: NonZeroU32::new(width)?,
+            height: NonZeroU32::new(height)?,
+        });
+    }
+
+    let image = image.to_rgba8();
+    let (mut left, mut top, mut right, mut bottom) = (width, height, 0, 0);
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if pixel.0[3] > threshold {
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x + 1);
+            bottom = bottom.max(y + 1);
+        }
+    }
+
+    Some(Rect {
+        left,
+        top,
+        width: NonZeroU32::new(right.saturating_sub(left))?,
+        height: NonZeroU32::new(bottom.saturating_sub(top))?,
+    })
+}
+
+/// すべての画像で、透明度がしきい値を超える画素を囲む矩形範囲を返す関数。
+/// そのような画素がどの画像にもない場合は`None`を返す。
+fn union_opaque_bounds(images: &[&DynamicImage], threshold: u8) -> Option<Rect> {
+    let (left, top, right, bottom) = images
+        .iter()
+        .filter_map(|v| opaque_bounds(v, threshold))
+        .map(|v| {
+            (
+                v.left,
+                v.top,
+                v.left + v.width.get(),
+                v.top + v.height.get(),
+            )
+        })
+        .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)))?;
+
+    Some(Rect {
+        left,
+        top,
+        width: NonZeroU32::new(right - left)?,
+        height: NonZeroU32::new(bottom - top)?,
+    })
+}
+
+/// 画像を読み込み、透明度がしきい値を超える画素を囲む矩形範囲を返す関数。
+/// `Trim`と同じ範囲になるよう、PNAと透過色と向きを設定のとおりに反映してから調べる。
+pub(crate) fn get_opaque_bounds(
+    src_path: &PathBuf,
+    threshold: u8,
+    options: &ResizeOptions,
+) -> Result<Option<Rect>, ResizedPngError> {
+    let frames = read_prepared_frames(src_path, options)?;
+    let images: Vec<&DynamicImage> = frames.iter().map(|(v, _)| v).collect();
+
+    Ok(union_opaque_bounds(&images, threshold))
+}
+
+/// すべての画像で、透明な余白を除いて切り取る矩形範囲を返す関数。
+/// 画像はすべて同じ大きさとして、どの画像の不透明な画素も含む範囲にする。
+/// 余白の幅だけ外側を残し、画像からはみ出す部分は画像の端までにする。
+/// すべての画素が透明な場合や、切り取る余白がない場合は`None`を返す。
+pub(crate) fn trim_rect(images: &[&DynamicImage], margin: u32, threshold: u8) -> Option<Rect> {
+    let (width, height) = images.first().map(|v| (v.width(), v.height()))?;
+
+    let bounds = union_opaque_bounds(images, threshold)?;
+    let (left, top) = (bounds.left, bounds.top);
+    let right = left + bounds.width.get();
+    let bottom = top + bounds.height.get();
+
+    let left = left.saturating_sub(margin);
+    let top = top.saturating_sub(margin);
+    let right = right.saturating_add(margin).min(width);
+    let bottom = bottom.saturating_add(margin).min(height);
+
+    if (left, top, right, bottom) == (0, 0, width, height) {
+        return None;
+    }
+
+    Some(Rect {
+        left,
+        top,
+        width: NonZeroU32::new(right - left)?,
+        height: NonZeroU32::new(bottom - top)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::{Rgba, RgbaImage};
+
+    /// 透明な余白の内側に、半透明と不透明の画素を置いた画像
+    fn framed_image() -> DynamicImage {
+        let mut image = RgbaImage::from_pixel(10, 8, Rgba([0, 0, 0, 0]));
+        image.put_pixel(2, 3, Rgba([255, 0, 0, 100]));
+        image.put_pixel(6, 5, Rgba([255, 0, 0, 255]));
+
+        DynamicImage::ImageRgba8(image)
+    }
+
+    mod opaque_bounds {
+        use super::*;
+
+        #[test]
+        fn bounds_of_pixels_above_threshold() {
+            assert_eq!(
+                opaque_bounds(&framed_image(), 0),
+                Some(Rect::from_strs("2", "3", "5", "3").unwrap())
+            );
+            assert_eq!(
+                opaque_bounds(&framed_image(), 100),
+                Some(Rect::from_strs("6", "5", "1", "1").unwrap())
+            );
+        }
+
+        #[test]
+        fn none_when_all_pixels_are_transparent() {
+            assert_eq!(opaque_bounds(&framed_image(), 255), None);
+        }
+
+        #[test]
+        fn whole_image_when_image_has_no_alpha() {
+            let image = DynamicImage::new_rgb8(4, 3);
+
+            assert_eq!(
+                opaque_bounds(&image, 0),
+                Some(Rect::from_strs("0", "0", "4", "3").unwrap())
+            );
+        }
+    }
+
+    mod get_opaque_bounds {
+        use super::*;
+
+        use crate::orientation::{Flip, Rotation};
+        use crate::resized_png::{to_resized_png, SizeCommand};
+        use tempfile::tempdir;
+
+        #[test]
+        fn same_size_as_trimmed_image_when_options_are_specified() {
+            let out_dir = tempdir().unwrap();
+            let src_path = out_dir.path().join("framed.png");
+            framed_image().save(&src_path).unwrap();
+            let dist_path = out_dir.path().join("output.png");
+
+            for (options, expected) in [
+                (ResizeOptions::default(), ("2", "3", "5", "3")),
+                (
+                    ResizeOptions {
+                        rotation: Rotation::Rotate90,
+                        ..Default::default()
+                    },
+                    ("2", "2", "3", "5"),
+                ),
+                (
+                    ResizeOptions {
+                        flip: Flip::Horizontal,
+                        ..Default::default()
+                    },
+                    ("3", "3", "5", "3"),
+                ),
+            ] {
+                let bounds = get_opaque_bounds(&src_path, 0, &options).unwrap().unwrap();
+                assert_eq!(
+                    bounds,
+                    Rect::from_strs(expected.0, expected.1, expected.2, expected.3).unwrap()
+                );
+
+                let options = ResizeOptions {
+                    trim: Some(0),
+                    ..options
+                };
+                to_resized_png(
+                    &src_path,
+                    &dist_path,
+                    SizeCommand::Pixel(0),
+                    SizeCommand::Pixel(0),
+                    &options,
+                )
+                .unwrap();
+                let output = image::open(&dist_path).unwrap();
+                assert_eq!(
+                    (output.width(), output.height()),
+                    (bounds.width.get(), bounds.height.get())
+                );
+            }
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn bounds_without_key_color_when_color_key_is_enabled() {
+            let out_dir = tempdir().unwrap();
+            let src_path = out_dir.path().join("keyed.png");
+            let mut image = image::RgbImage::from_pixel(10, 8, image::Rgb([0, 255, 0]));
+            image.put_pixel(4, 2, image::Rgb([255, 0, 0]));
+            image.save(&src_path).unwrap();
+
+            let options = ResizeOptions {
+                color_key: true,
+                ..Default::default()
+            };
+
+            assert_eq!(
+                get_opaque_bounds(&src_path, 0, &Default::default()).unwrap(),
+                Some(Rect::from_strs("0", "0", "10", "8").unwrap())
+            );
+            assert_eq!(
+                get_opaque_bounds(&src_path, 0, &options).unwrap(),
+                Some(Rect::from_strs("4", "2", "1", "1").unwrap())
+            );
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn failed_when_file_does_not_exist() {
+            let out_dir = tempdir().unwrap();
+
+            assert!(matches!(
+                get_opaque_bounds(
+                    &out_dir.path().join("not_exist.png"),
+                    0,
+                    &Default::default()
+                ),
+                Err(ResizedPngError::NotFound)
+            ));
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod trim_rect {
+        use super::*;
+
+        #[test]
+        fn rect_without_transparent_margin() {
+            assert_eq!(
+                trim_rect(&[&framed_image()], 0, 0),
+                Some(Rect::from_strs("2", "3", "5", "3").unwrap())
+            );
+        }
+
+        #[test]
+        fn margin_kept_inside_of_image_when_margin_is_specified() {
+            // 左は2、下は2までしか余白がない。
+            assert_eq!(
+                trim_rect(&[&framed_image()], 2, 0),
+                Some(Rect::from_strs("0", "1", "9", "7").unwrap())
+            );
+        }
+
+        #[test]
+        fn none_when_all_pixels_are_transparent() {
+            assert_eq!(trim_rect(&[&framed_image()], 0, 255), None);
+        }
+
+        #[test]
+        fn union_of_bounds_when_images_are_multiple() {
+            let mut image = RgbaImage::from_pixel(10, 8, Rgba([0, 0, 0, 0]));
+            image.put_pixel(8, 1, Rgba([0, 0, 255, 255]));
+            let image = DynamicImage::ImageRgba8(image);
+
+            assert_eq!(
+                trim_rect(&[&framed_image(), &image], 0, 0),
+                Some(Rect::from_strs("2", "1", "7", "5").unwrap())
+            );
+        }
+    }
+}
@@ -0,0 +1,350 @@
+use std::num::NonZeroU32;
+
+use fast_image_resize as fir;
+use image::{ColorType, DynamicImage, ImageBuffer, Rgba, RgbaImage};
+
+use crate::error::ResizedPngError;
+
+/// 名前から色の種類を読み取る関数。
+pub(crate) fn color_type_from_name(name: &str) -> Option<ColorType> {
+    match name {
+        "L8" => Some(ColorType::L8),
+        "La8" => Some(ColorType::La8),
+        "Rgb8" => Some(ColorType::Rgb8),
+        "Rgba8" => Some(ColorType::Rgba8),
+        "L16" => Some(ColorType::L16),
+        "La16" => Some(ColorType::La16),
+        "Rgb16" => Some(ColorType::Rgb16),
+        "Rgba16" => Some(ColorType::Rgba16),
+        _ => None,
+    }
+}
+
+/// 色の種類の名前を返す関数。
+/// `ColorType`で指定できる名前に加えて、浮動小数点数の種類も返す。
+pub(crate) fn color_type_name(color_type: ColorType) -> &'static str {
+    match color_type {
+        ColorType::L8 => "L8",
+        ColorType::La8 => "La8",
+        ColorType::Rgb8 => "Rgb8",
+        ColorType::Rgba8 => "Rgba8",
+        ColorType::L16 => "L16",
+        ColorType::La16 => "La16",
+        ColorType::Rgb16 => "Rgb16",
+        ColorType::Rgba16 => "Rgba16",
+        ColorType::Rgb32F => "Rgb32F",
+        ColorType::Rgba32F => "Rgba32F",
+        _ => "Unknown",
+    }
+}
+
+/// 1チャンネルが8bitを超える色の種類かを返す関数。
+/// 浮動小数点数の種類も、16bitとして扱うため`true`を返す。
+pub(crate) fn is_16bit(color_type: ColorType) -> bool {
+    color_type.bytes_per_pixel() > color_type.channel_count()
+}
+
+/// 画像を指定された色の種類に変換する関数。
+pub(crate) fn convert_color(image: DynamicImage, color_type: ColorType) -> DynamicImage {
+    if image.color() == color_type {
+        return image;
+    }
+
+    match color_type {
+        ColorType::L8 => DynamicImage::ImageLuma8(image.to_luma8()),
+        ColorType::La8 => DynamicImage::ImageLumaA8(image.to_luma_alpha8()),
+        ColorType::Rgb8 => DynamicImage::ImageRgb8(image.to_rgb8()),
+        ColorType::L16 => DynamicImage::ImageLuma16(image.to_luma16()),
+        ColorType::La16 => DynamicImage::ImageLumaA16(image.to_luma_alpha16()),
+        ColorType::Rgb16 => DynamicImage::ImageRgb16(image.to_rgb16()),
+        ColorType::Rgba16 => DynamicImage::ImageRgba16(image.to_rgba16()),
+        _ => DynamicImage::ImageRgba8(image.to_rgba8()),
+    }
+}
+
+/// 画像をfirの画像に変換する関数。
+/// 色の種類はできるだけそのまま保ち、浮動小数点などは16bitにする。
+pub(crate) fn to_fir_image(image: DynamicImage) -> Result<fir::Image<'static>, ResizedPngError> {
+    let (width, height) = NonZeroU32::new(image.width())
+        .zip(NonZeroU32::new(image.height()))
+        .ok_or(ResizedPngError::InputSizeError)?;
+
+    let (buffer, pixel_type) = match image {
+        DynamicImage::ImageLuma8(v) => (v.into_raw(), fir::PixelType::U8),
+        DynamicImage::ImageLumaA8(v) => (v.into_raw(), fir::PixelType::U8x2),
+        DynamicImage::ImageRgb8(v) => (v.into_raw(), fir::PixelType::U8x3),
+        DynamicImage::ImageRgba8(v) => (v.into_raw(), fir::PixelType::U8x4),
+        DynamicImage::ImageLuma16(v) => (u16_to_bytes(&v.into_raw()), fir::PixelType::U16),
+        DynamicImage::ImageLumaA16(v) => (u16_to_bytes(&v.into_raw()), fir::PixelType::U16x2),
+        DynamicImage::ImageRgb16(v) => (u16_to_bytes(&v.into_raw()), fir::PixelType::U16x3),
+        DynamicImage::ImageRgba16(v) => (u16_to_bytes(&v.into_raw()), fir::PixelType::U16x4),
+        v if v.color().has_alpha() => (
+            u16_to_bytes(&v.to_rgba16().into_raw()),
+            fir::PixelType::U16x4,
+        ),
+        v => (
+            u16_to_bytes(&v.to_rgb16().into_raw()),
+            fir::PixelType::U16x3,
+        ),
+    };
+
+    Ok(fir::Image::from_vec_u8(width, height, buffer, pixel_type)?)
+}
+
+/// firの画像を画像に戻す関数。
+pub(crate) fn to_dynamic_image(image: fir::Image) -> DynamicImage {
+    let (width, height) = (image.width().get(), image.height().get());
+    let pixel_type = image.pixel_type();
+    let buffer = image.into_vec();
+
+    macro_rules! image_8 {
+        ($variant: path) => {
+            $variant(ImageBuffer::from_raw(width, height, buffer).expect("buffer size is same"))
+        };
+    }
+    macro_rules! image_16 {
+        ($variant: path) => {
+            $variant(
+                ImageBuffer::from_raw(width, height, bytes_to_u16(&buffer))
+                    .expect("buffer size is same"),
+            )
+        };
+    }
+
+    match pixel_type {
+        fir::PixelType::U8 => image_8!(DynamicImage::ImageLuma8),
+        fir::PixelType::U8x2 => image_8!(DynamicImage::ImageLumaA8),
+        fir::PixelType::U8x3 => image_8!(DynamicImage::ImageRgb8),
+        fir::PixelType::U16 => image_16!(DynamicImage::ImageLuma16),
+        fir::PixelType::U16x2 => image_16!(DynamicImage::ImageLumaA16),
+        fir::PixelType::U16x3 => image_16!(DynamicImage::ImageRgb16),
+        fir::PixelType::U16x4 => image_16!(DynamicImage::ImageRgba16),
+        _ => image_8!(DynamicImage::ImageRgba8),
+    }
+}
+
+/// 画素の種類がアルファ値を持つかどうかを返す関数。
+pub(crate) fn has_alpha(pixel_type: fir::PixelType) -> bool {
+    matches!(
+        pixel_type,
+        fir::PixelType::U8x2 | fir::PixelType::U8x4 | fir::PixelType::U16x2 | fir::PixelType::U16x4
+    )
+}
+
+/// 画素の種類に対応する色の種類を返す関数。
+pub(crate) fn color_type_of(pixel_type: fir::PixelType) -> ColorType {
+    match pixel_type {
+        fir::PixelType::U8 => ColorType::L8,
+        fir::PixelType::U8x2 => ColorType::La8,
+        fir::PixelType::U8x3 => ColorType::Rgb8,
+        fir::PixelType::U16 => ColorType::L16,
+        fir::PixelType::U16x2 => ColorType::La16,
+        fir::PixelType::U16x3 => ColorType::Rgb16,
+        fir::PixelType::U16x4 => ColorType::Rgba16,
+        _ => ColorType::Rgba8,
+    }
+}
+
+/// 画素の種類に対応する、アルファ値を持つ色の種類を返す関数。
+pub(crate) fn color_type_with_alpha(pixel_type: fir::PixelType) -> ColorType {
+    match pixel_type {
+        fir::PixelType::U8 | fir::PixelType::U8x2 => ColorType::La8,
+        fir::PixelType::U16 | fir::PixelType::U16x2 => ColorType::La16,
+        fir::PixelType::U16x3 | fir::PixelType::U16x4 => ColorType::Rgba16,
+        _ => ColorType::Rgba8,
+    }
+}
+
+/// 画素の種類に対応する、1成分あたり16bitの画素の種類を返す関数。
+pub(crate) fn to_u16_pixel_type(pixel_type: fir::PixelType) -> fir::PixelType {
+    match pixel_type {
+        fir::PixelType::U8 => fir::PixelType::U16,
+        fir::PixelType::U8x2 => fir::PixelType::U16x2,
+        fir::PixelType::U8x3 => fir::PixelType::U16x3,
+        fir::PixelType::U8x4 => fir::PixelType::U16x4,
+        v => v,
+    }
+}
+
+/// RGBAの色を、指定された画素の種類のバイト列にする関数。
+pub(crate) fn pixel_bytes(color: [u8; 4], pixel_type: fir::PixelType) -> Vec<u8> {
+    let pixel = DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba(color)));
+
+    to_fir_image(convert_color(pixel, color_type_of(pixel_type)))
+        .expect("size of pixel is not 0")
+        .into_vec()
+}
+
+fn u16_to_bytes(values: &[u16]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_ne_bytes()).collect()
+}
+
+fn bytes_to_u16(bytes: &[u8]) -> Vec<u16> {
+    bytes
+        .chunks_exact(2)
+        .map(|v| u16::from_ne_bytes([v[0], v[1]]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::{GrayImage, ImageBuffer, Luma, Rgb32FImage};
+
+    mod color_type_from_name {
+        use super::*;
+
+        #[test]
+        fn checking_value_of_each_name() {
+            assert_eq!(color_type_from_name("L8"), Some(ColorType::L8));
+            assert_eq!(color_type_from_name("La8"), Some(ColorType::La8));
+            assert_eq!(color_type_from_name("Rgb8"), Some(ColorType::Rgb8));
+            assert_eq!(color_type_from_name("Rgba8"), Some(ColorType::Rgba8));
+            assert_eq!(color_type_from_name("L16"), Some(ColorType::L16));
+            assert_eq!(color_type_from_name("La16"), Some(ColorType::La16));
+            assert_eq!(color_type_from_name("Rgb16"), Some(ColorType::Rgb16));
+            assert_eq!(color_type_from_name("Rgba16"), Some(ColorType::Rgba16));
+            assert_eq!(color_type_from_name("Rgb32F"), None);
+        }
+    }
+
+    mod color_type_name {
+        use super::*;
+
+        #[test]
+        fn same_name_as_color_type_from_name() {
+            for name in [
+                "L8", "La8", "Rgb8", "Rgba8", "L16", "La16", "Rgb16", "Rgba16",
+            ] {
+                let color_type = color_type_from_name(name).unwrap();
+
+                assert_eq!(color_type_name(color_type), name);
+            }
+            assert_eq!(color_type_name(ColorType::Rgba32F), "Rgba32F");
+        }
+    }
+
+    mod is_16bit {
+        use super::*;
+
+        #[test]
+        fn checking_value_of_each_color_type() {
+            assert!(!is_16bit(ColorType::L8));
+            assert!(!is_16bit(ColorType::Rgba8));
+            assert!(is_16bit(ColorType::La16));
+            assert!(is_16bit(ColorType::Rgb16));
+            assert!(is_16bit(ColorType::Rgba32F));
+        }
+    }
+
+    mod to_fir_image {
+        use super::*;
+
+        #[test]
+        fn same_image_when_converted_back() {
+            let gray = GrayImage::from_fn(3, 2, |x, y| Luma([(x * 10 + y) as u8]));
+            let gray16: ImageBuffer<Luma<u16>, Vec<u16>> =
+                ImageBuffer::from_fn(3, 2, |x, y| Luma([(x * 1000 + y) as u16]));
+            let images = [
+                DynamicImage::ImageLuma8(gray.clone()),
+                DynamicImage::ImageLuma8(gray).to_luma_alpha8().into(),
+                DynamicImage::ImageLuma16(gray16.clone()),
+                DynamicImage::ImageLuma16(gray16.clone())
+                    .to_luma_alpha16()
+                    .into(),
+                DynamicImage::ImageLuma16(gray16.clone()).to_rgb16().into(),
+                DynamicImage::ImageLuma16(gray16.clone()).to_rgba16().into(),
+                DynamicImage::ImageLuma16(gray16.clone()).to_rgb8().into(),
+                DynamicImage::ImageLuma16(gray16).to_rgba8().into(),
+            ];
+
+            for image in images {
+                let color_type = image.color();
+                let fir_image = to_fir_image(image.clone()).unwrap();
+
+                assert_eq!(to_dynamic_image(fir_image), image, "{:?}", color_type);
+            }
+        }
+
+        #[test]
+        fn pixel_type_of_each_color_type() {
+            let image = DynamicImage::new_luma8(1, 1);
+            assert_eq!(
+                to_fir_image(image).unwrap().pixel_type(),
+                fir::PixelType::U8
+            );
+
+            let image = DynamicImage::new_rgb8(1, 1);
+            assert_eq!(
+                to_fir_image(image).unwrap().pixel_type(),
+                fir::PixelType::U8x3
+            );
+
+            let image = DynamicImage::new_luma_a16(1, 1);
+            assert_eq!(
+                to_fir_image(image).unwrap().pixel_type(),
+                fir::PixelType::U16x2
+            );
+        }
+
+        #[test]
+        fn u16_when_color_type_is_float() {
+            let image = DynamicImage::ImageRgb32F(Rgb32FImage::new(1, 1));
+            assert_eq!(
+                to_fir_image(image).unwrap().pixel_type(),
+                fir::PixelType::U16x3
+            );
+
+            let image = DynamicImage::new_rgba32f(1, 1);
+            assert_eq!(
+                to_fir_image(image).unwrap().pixel_type(),
+                fir::PixelType::U16x4
+            );
+        }
+
+        #[test]
+        fn failed_when_image_is_empty() {
+            let image = DynamicImage::new_rgb8(0, 1);
+
+            assert!(matches!(
+                to_fir_image(image),
+                Err(ResizedPngError::InputSizeError)
+            ));
+        }
+    }
+
+    mod pixel_bytes {
+        use super::*;
+
+        #[test]
+        fn checking_bytes_of_each_pixel_type() {
+            let color = [255, 255, 255, 128];
+
+            assert_eq!(pixel_bytes(color, fir::PixelType::U8), [255]);
+            assert_eq!(pixel_bytes(color, fir::PixelType::U8x2), [255, 128]);
+            assert_eq!(pixel_bytes(color, fir::PixelType::U8x3), [255, 255, 255]);
+            assert_eq!(pixel_bytes(color, fir::PixelType::U8x4), color);
+            assert_eq!(
+                pixel_bytes(color, fir::PixelType::U16x2),
+                u16_to_bytes(&[65535, 32896])
+            );
+        }
+    }
+
+    mod has_alpha {
+        use super::*;
+
+        #[test]
+        fn checking_value_of_each_pixel_type() {
+            assert!(!has_alpha(fir::PixelType::U8));
+            assert!(has_alpha(fir::PixelType::U8x2));
+            assert!(!has_alpha(fir::PixelType::U8x3));
+            assert!(has_alpha(fir::PixelType::U8x4));
+            assert!(!has_alpha(fir::PixelType::U16));
+            assert!(has_alpha(fir::PixelType::U16x2));
+            assert!(!has_alpha(fir::PixelType::U16x3));
+            assert!(has_alpha(fir::PixelType::U16x4));
+        }
+    }
+}
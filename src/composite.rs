@@ -0,0 +1,345 @@
+use std::path::{Path, PathBuf};
+
+use image::{DynamicImage, RgbaImage};
+
+use crate::error::ResizedPngError;
+use crate::pna::merge_pna;
+use crate::resized_png::{decode_image, save_png, MAX_OUTPUT_SIZE};
+
+/// 重ねる画像の色の混ぜ方
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub(crate) enum BlendMode {
+    /// 通常
+    #[default]
+    Normal,
+    /// 乗算
+    Multiply,
+    /// スクリーン
+    Screen,
+    /// 加算
+    Add,
+    /// オーバーレイ
+    Overlay,
+}
+
+impl BlendMode {
+    pub(crate) fn from_name(name: &str) -> Option<BlendMode> {
+        match name {
+            "Normal" => Some(BlendMode::Normal),
+            "Multiply" => Some(BlendMode::Multiply),
+            "Screen" => Some(BlendMode::Screen),
+            "Add" => Some(BlendMode::Add),
+            "Overlay" => Some(BlendMode::Overlay),
+            _ => None,
+        }
+    }
+
+    /// 下の色と重ねる色(0から1)を混ぜた色を返す関数。
+    fn blend(self, base: f32, overlay: f32) -> f32 {
+        match self {
+            BlendMode::Normal => overlay,
+            BlendMode::Multiply => base * overlay,
+            BlendMode::Screen => base + overlay - base * overlay,
+            BlendMode::Add => (base + overlay).min(1.0),
+            BlendMode::Overlay if base <= 0.5 => 2.0 * base * overlay,
+            BlendMode::Overlay => 1.0 - 2.0 * (1.0 - base) * (1.0 - overlay),
+        }
+    }
+}
+
+/// 重ねる画像と、その位置と混ぜ方
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct Layer {
+    pub(crate) path: PathBuf,
+    /// 下の画像の左端からの位置(負の数も可、`±MAX_OUTPUT_SIZE`まで)
+    pub(crate) x: i64,
+    /// 下の画像の上端からの位置(負の数も可、`±MAX_OUTPUT_SIZE`まで)
+    pub(crate) y: i64,
+    pub(crate) mode: BlendMode,
+}
+
+impl Layer {
+    /// 位置と混ぜ方の文字列から、重ねる画像を読み取る関数。
+    pub(crate) fn from_strs(
+        path: PathBuf,
+        x: &str,
+        y: &str,
+        mode: &str,
+    ) -> Result<Layer, ResizedPngError> {
+        // 位置は、出力できる画像の大きさの範囲に限る。
+        let parse_position = |v: &str| {
+            v.parse::<i64>()
+                .ok()
+                .filter(|v| v.abs() <= MAX_OUTPUT_SIZE as i64)
+                .ok_or(ResizedPngError::InvalidOption)
+        };
+
+        Ok(Layer {
+            path,
+            x: parse_position(x)?,
+            y: parse_position(y)?,
+            mode: BlendMode::from_name(mode).ok_or(ResizedPngError::InvalidOption)?,
+        })
+    }
+}
+
+/// 下の画像に画像を順に重ねて、pngとして出力する関数。
+/// 出力する画像の大きさは下の画像と同じで、はみ出した部分は切り取る。
+pub(crate) fn composite_to_png(
+    base_path: &PathBuf,
+    dist_path: &Path,
+    layers: &[Layer],
+) -> Result<(), ResizedPngError> {
+    let mut image = read_rgba_image(base_path)?;
+
+    for layer in layers.iter() {
+        let overlay = read_rgba_image(&layer.path)?;
+        composite_image(&mut image, &overlay, layer.x, layer.y, layer.mode);
+    }
+
+    save_png(DynamicImage::ImageRgba8(image), dist_path)
+}
+
+/// 画像を読み込み、PNAがあれば透明度として読み込む関数。
+fn read_rgba_image(src_path: &PathBuf) -> Result<RgbaImage, ResizedPngError> {
+    let image = decode_image(src_path)?;
+
+    Ok(merge_pna(image, src_path)?.to_rgba8())
+}
+
+/// 下の画像の指定された位置に画像を重ねる関数。
+/// 下の画像と重なる範囲だけを処理する。
+fn composite_image(base: &mut RgbaImage, overlay: &RgbaImage, x: i64, y: i64, mode: BlendMode) {
+    let left = x.max(0);
+    let top = y.max(0);
+    let right = (x + overlay.width() as i64).min(base.width() as i64);
+    let bottom = (y + overlay.height() as i64).min(base.height() as i64);
+
+    for base_y in top..bottom {
+        for base_x in left..right {
+            let pixel = overlay.get_pixel((base_x - x) as u32, (base_y - y) as u32);
+            let base_pixel = base.get_pixel_mut(base_x as u32, base_y as u32);
+            base_pixel.0 = blend_pixel(base_pixel.0, pixel.0, mode);
+        }
+    }
+}
+
+/// 下の画素に画素を重ねた色を返す関数。
+/// 透明度を持つ場合は、W3CのCompositing and Blendingの式で合成する。
+pub(crate) fn blend_pixel(base: [u8; 4], overlay: [u8; 4], mode: BlendMode) -> [u8; 4] {
+    let base_alpha = base[3] as f32 / 255.0;
+    let overlay_alpha = overlay[3] as f32 / 255.0;
+
+    let alpha = overlay_alpha + base_alpha * (1.0 - overlay_alpha);
+    if alpha <= 0.0 {
+        return [0, 0, 0, 0];
+    }
+
+    let mut output = [0; 4];
+    for i in 0..3 {
+        let base_color = base[i] as f32 / 255.0;
+        let overlay_color = overlay[i] as f32 / 255.0;
+
+        // 下の画素が透明なほど、重ねる色をそのまま使う。
+        let blended =
+            (1.0 - base_alpha) * overlay_color + base_alpha * mode.blend(base_color, overlay_color);
+        let color =
+            (overlay_alpha * blended + base_alpha * base_color * (1.0 - overlay_alpha)) / alpha;
+
+        output[i] = (color * 255.0).round().clamp(0.0, 255.0) as u8;
+    }
+    output[3] = (alpha * 255.0).round() as u8;
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod blend_mode {
+        use super::*;
+
+        #[test]
+        fn checking_value_of_each_name() {
+            assert_eq!(BlendMode::from_name("Normal"), Some(BlendMode::Normal));
+            assert_eq!(BlendMode::from_name("Multiply"), Some(BlendMode::Multiply));
+            assert_eq!(BlendMode::from_name("Screen"), Some(BlendMode::Screen));
+            assert_eq!(BlendMode::from_name("Add"), Some(BlendMode::Add));
+            assert_eq!(BlendMode::from_name("Overlay"), Some(BlendMode::Overlay));
+            assert_eq!(BlendMode::from_name("Darken"), None);
+        }
+    }
+
+    mod layer {
+        use super::*;
+
+        #[test]
+        fn success_when_strs_are_valid() {
+            let layer = Layer::from_strs(PathBuf::from("a.png"), "-10", "20", "Screen").unwrap();
+
+            assert_eq!((layer.x, layer.y, layer.mode), (-10, 20, BlendMode::Screen));
+        }
+
+        #[test]
+        fn failed_when_strs_are_invalid() {
+            for (x, y, mode) in [
+                ("a", "0", "Normal"),
+                ("0", "1.5", "Normal"),
+                ("0", "0", ""),
+                ("9223372036854775807", "0", "Normal"),
+                ("0", "-16385", "Normal"),
+            ] {
+                assert!(matches!(
+                    Layer::from_strs(PathBuf::from("a.png"), x, y, mode),
+                    Err(ResizedPngError::InvalidOption)
+                ));
+            }
+        }
+    }
+
+    mod composite_image {
+        use super::*;
+
+        use image::Rgba;
+
+        #[test]
+        fn only_overlapped_area_changed_when_overlay_is_partly_outside() {
+            let mut base = RgbaImage::from_pixel(4, 3, Rgba([255, 255, 255, 255]));
+            let overlay = RgbaImage::from_fn(3, 3, |x, y| Rgba([x as u8, y as u8, 0, 255]));
+
+            composite_image(&mut base, &overlay, 2, -1, BlendMode::Normal);
+
+            assert_eq!(base.get_pixel(2, 0).0, [0, 1, 0, 255]);
+            assert_eq!(base.get_pixel(3, 1).0, [1, 2, 0, 255]);
+            assert_eq!(base.get_pixel(1, 0).0, [255, 255, 255, 255]);
+            assert_eq!(base.get_pixel(2, 2).0, [255, 255, 255, 255]);
+        }
+
+        #[test]
+        fn same_as_base_when_overlay_is_outside() {
+            let base = RgbaImage::from_pixel(4, 3, Rgba([255, 255, 255, 255]));
+            let overlay = RgbaImage::from_pixel(3, 3, Rgba([0, 0, 0, 255]));
+
+            for (x, y) in [(-3, 0), (4, 0), (0, -3), (0, 3), (-16384, 16384)] {
+                let mut output = base.clone();
+                composite_image(&mut output, &overlay, x, y, BlendMode::Normal);
+
+                assert_eq!(output, base, "{}, {}", x, y);
+            }
+        }
+    }
+
+    mod blend_pixel {
+        use super::*;
+
+        #[test]
+        fn checking_value_of_each_mode_when_pixels_are_opaque() {
+            let base = [200, 100, 0, 255];
+            let overlay = [100, 100, 100, 255];
+
+            assert_eq!(
+                blend_pixel(base, overlay, BlendMode::Normal),
+                [100, 100, 100, 255]
+            );
+            assert_eq!(
+                blend_pixel(base, overlay, BlendMode::Multiply),
+                [78, 39, 0, 255]
+            );
+            assert_eq!(
+                blend_pixel(base, overlay, BlendMode::Screen),
+                [222, 161, 100, 255]
+            );
+            assert_eq!(
+                blend_pixel(base, overlay, BlendMode::Add),
+                [255, 200, 100, 255]
+            );
+            assert_eq!(
+                blend_pixel(base, overlay, BlendMode::Overlay),
+                [188, 78, 0, 255]
+            );
+        }
+
+        #[test]
+        fn same_as_base_when_overlay_is_transparent() {
+            let base = [200, 100, 0, 128];
+
+            assert_eq!(blend_pixel(base, [1, 2, 3, 0], BlendMode::Multiply), base);
+        }
+
+        #[test]
+        fn overlay_color_when_base_is_transparent() {
+            let overlay = [10, 20, 30, 128];
+
+            assert_eq!(
+                blend_pixel([255, 255, 255, 0], overlay, BlendMode::Multiply),
+                overlay
+            );
+        }
+
+        #[test]
+        fn mixed_color_when_overlay_is_semi_transparent() {
+            assert_eq!(
+                blend_pixel([0, 0, 0, 255], [255, 255, 255, 51], BlendMode::Normal),
+                [51, 51, 51, 255]
+            );
+        }
+    }
+
+    mod composite_to_png {
+        use super::*;
+
+        use image::Rgba;
+        use tempfile::tempdir;
+
+        #[test]
+        fn overlays_clipped_to_base_when_overlays_are_placed() {
+            let out_dir = tempdir().unwrap();
+            let base_path = out_dir.path().join("base.png");
+            RgbaImage::from_pixel(4, 4, Rgba([255, 255, 255, 255]))
+                .save(&base_path)
+                .unwrap();
+            let overlay_path = out_dir.path().join("overlay.png");
+            RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255]))
+                .save(&overlay_path)
+                .unwrap();
+            let dist_path = out_dir.path().join("output.png");
+            let layers = vec![
+                Layer::from_strs(overlay_path.clone(), "-1", "-1", "Normal").unwrap(),
+                Layer::from_strs(overlay_path, "3", "3", "Multiply").unwrap(),
+            ];
+
+            composite_to_png(&base_path, &dist_path, &layers).unwrap();
+
+            let output = image::open(&dist_path).unwrap().to_rgba8();
+            assert_eq!(output.dimensions(), (4, 4));
+            assert_eq!(output.get_pixel(0, 0).0, [255, 0, 0, 255]);
+            assert_eq!(output.get_pixel(1, 1).0, [255, 255, 255, 255]);
+            assert_eq!(output.get_pixel(3, 3).0, [255, 0, 0, 255]);
+            assert_eq!(output.get_pixel(2, 2).0, [255, 255, 255, 255]);
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn failed_when_overlay_does_not_exist() {
+            let out_dir = tempdir().unwrap();
+            let base_path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.png");
+            let dist_path = out_dir.path().join("output.png");
+            let layers =
+                vec![
+                    Layer::from_strs(out_dir.path().join("not_exist.png"), "0", "0", "Normal")
+                        .unwrap(),
+                ];
+
+            assert!(matches!(
+                composite_to_png(&base_path, &dist_path, &layers),
+                Err(ResizedPngError::NotFound)
+            ));
+            assert!(!dist_path.exists());
+
+            out_dir.close().unwrap();
+        }
+    }
+}
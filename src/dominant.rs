@@ -0,0 +1,270 @@
+use std::{num::NonZeroU32, path::PathBuf};
+
+use fast_image_resize as fir;
+use image::io::Reader as ImageReader;
+
+use crate::error::ResizedPngError;
+use crate::pna::merge_pna;
+
+/// 色を調べる前に縮小する大きさ(横幅と縦幅の最大値)
+const SAMPLE_SIZE: u32 = 64;
+
+/// 取り出せる色の数の最大値
+pub(crate) const MAX_DOMINANT_COLORS: usize = 256;
+
+/// 画像の主な色
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct DominantColor {
+    /// 色(RGB)
+    pub(crate) color: [u8; 3],
+    /// 画像の中で占める割合(0から1)
+    pub(crate) proportion: f64,
+}
+
+/// 画像の平均の色と、割合の大きい順に主な色を返す関数。
+/// PNAがあれば透明度として読み込む。完全に透明な画素は無視し、すべて透明な場合は平均の色を`None`にする。
+pub(crate) fn get_dominant_colors(
+    src_path: &PathBuf,
+    count: usize,
+) -> Result<(Option<[u8; 3]>, Vec<DominantColor>), ResizedPngError> {
+    let image = ImageReader::open(src_path)?
+        .with_guessed_format()?
+        .decode()?;
+    let image = merge_pna(image, src_path)?.to_rgba8();
+    let (width, height) = image.dimensions();
+    let image = fir::Image::from_vec_u8(
+        NonZeroU32::new(width).ok_or(ResizedPngError::InputSizeError)?,
+        NonZeroU32::new(height).ok_or(ResizedPngError::InputSizeError)?,
+        image.into_raw(),
+        fir::PixelType::U8x4,
+    )?;
+
+    let pixels: Vec<[u8; 3]> = downsample(image)
+        .buffer()
+        .chunks_exact(4)
+        .filter(|v| v[3] != 0)
+        .map(|v| [v[0], v[1], v[2]])
+        .collect();
+    if pixels.is_empty() {
+        return Ok((None, Vec::new()));
+    }
+
+    let total = pixels.len();
+    let average = average_color(&pixels);
+
+    let mut boxes = median_cut(pixels, count);
+    boxes.sort_by_key(|v| std::cmp::Reverse(v.len()));
+    let colors = boxes
+        .iter()
+        .map(|v| DominantColor {
+            color: average_color(v),
+            proportion: v.len() as f64 / total as f64,
+        })
+        .collect();
+
+    Ok((Some(average), colors))
+}
+
+/// 画像が大きい場合に、縦横比を保って`SAMPLE_SIZE`以下に縮小する関数。
+fn downsample(mut image: fir::Image<'static>) -> fir::Image<'static> {
+    let (width, height) = (image.width().get(), image.height().get());
+    let scale = SAMPLE_SIZE as f64 / width.max(height) as f64;
+    if scale >= 1.0 {
+        return image;
+    }
+
+    let sample_width =
+        NonZeroU32::new(((width as f64 * scale).round() as u32).max(1)).expect("size is not zero");
+    let sample_height =
+        NonZeroU32::new(((height as f64 * scale).round() as u32).max(1)).expect("size is not zero");
+
+    // 透明な画素の色が混ざらないよう、アルファ値を乗算してから縮小する。
+    let alpha_mul_div = fir::MulDiv::default();
+    alpha_mul_div
+        .multiply_alpha_inplace(&mut image.view_mut())
+        .expect("limited target pixel type.");
+
+    let mut sample_image = fir::Image::new(sample_width, sample_height, image.pixel_type());
+    let mut resizer = fir::Resizer::new(fir::ResizeAlg::Convolution(fir::FilterType::Box));
+    resizer
+        .resize(&image.view(), &mut sample_image.view_mut())
+        .expect("pixel type is same");
+
+    alpha_mul_div
+        .divide_alpha_inplace(&mut sample_image.view_mut())
+        .expect("limited target pixel type.");
+
+    sample_image
+}
+
+/// メディアンカットで、画素を指定された数以下の箱に分ける関数。
+fn median_cut(pixels: Vec<[u8; 3]>, count: usize) -> Vec<Vec<[u8; 3]>> {
+    let mut boxes = vec![pixels];
+
+    while boxes.len() < count {
+        // 色の幅が一番広い箱を、その幅のチャンネルの中央値で分ける。
+        let Some((index, channel, _)) = boxes
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                let (channel, range) = widest_channel(v);
+                (i, channel, range)
+            })
+            .filter(|(_, _, range)| *range > 0)
+            .max_by_key(|(_, _, range)| *range)
+        else {
+            break;
+        };
+
+        let mut lower = boxes.swap_remove(index);
+        lower.sort_unstable_by_key(|v| v[channel]);
+
+        // 同じ色が2つの箱に分かれないよう、値の変わる位置で分ける。
+        let median = lower[lower.len() / 2][channel];
+        let at = match lower.partition_point(|v| v[channel] < median) {
+            0 => lower.partition_point(|v| v[channel] <= median),
+            v => v,
+        };
+        let upper = lower.split_off(at);
+
+        boxes.push(lower);
+        boxes.push(upper);
+    }
+
+    boxes
+}
+
+/// 値の幅が一番広いチャンネルと、その幅を返す関数。
+fn widest_channel(pixels: &[[u8; 3]]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let min = pixels.iter().map(|v| v[channel]).min().unwrap_or(0);
+            let max = pixels.iter().map(|v| v[channel]).max().unwrap_or(0);
+            (channel, max - min)
+        })
+        .max_by_key(|(_, range)| *range)
+        .expect("channels are not empty")
+}
+
+fn average_color(pixels: &[[u8; 3]]) -> [u8; 3] {
+    let mut sum = [0u64; 3];
+    for pixel in pixels.iter() {
+        for (s, v) in sum.iter_mut().zip(pixel.iter()) {
+            *s += *v as u64;
+        }
+    }
+
+    let len = pixels.len() as u64;
+    sum.map(|v| ((v + len / 2) / len) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::{Rgba, RgbaImage};
+    use tempfile::tempdir;
+
+    mod get_dominant_colors {
+        use super::*;
+
+        #[test]
+        fn colors_in_order_of_proportion_when_image_is_small() {
+            let out_dir = tempdir().unwrap();
+            let src_path = out_dir.path().join("input.png");
+            RgbaImage::from_fn(10, 10, |x, _| match x {
+                0..=4 => Rgba([255, 0, 0, 255]),
+                5..=7 => Rgba([0, 255, 0, 255]),
+                _ => Rgba([0, 0, 255, 255]),
+            })
+            .save(&src_path)
+            .unwrap();
+
+            let (average, colors) = get_dominant_colors(&src_path, 3).unwrap();
+
+            assert_eq!(average, Some([128, 77, 51]));
+            assert_eq!(
+                colors,
+                vec![
+                    DominantColor {
+                        color: [255, 0, 0],
+                        proportion: 0.5
+                    },
+                    DominantColor {
+                        color: [0, 255, 0],
+                        proportion: 0.3
+                    },
+                    DominantColor {
+                        color: [0, 0, 255],
+                        proportion: 0.2
+                    },
+                ]
+            );
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn transparent_pixels_ignored_when_image_is_large() {
+            let out_dir = tempdir().unwrap();
+            let src_path = out_dir.path().join("input.png");
+            RgbaImage::from_fn(128, 64, |x, _| match x {
+                0..=31 => Rgba([0, 0, 0, 0]),
+                32..=95 => Rgba([255, 0, 0, 255]),
+                _ => Rgba([0, 0, 255, 255]),
+            })
+            .save(&src_path)
+            .unwrap();
+
+            let (average, colors) = get_dominant_colors(&src_path, 4).unwrap();
+
+            assert_eq!(average, Some([170, 0, 85]));
+            assert_eq!(colors.len(), 2);
+            assert_eq!(colors[0].color, [255, 0, 0]);
+            assert!((colors[0].proportion - 2.0 / 3.0).abs() < 1e-9);
+            assert_eq!(colors[1].color, [0, 0, 255]);
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn no_colors_when_image_is_transparent() {
+            let out_dir = tempdir().unwrap();
+            let src_path = out_dir.path().join("input.png");
+            RgbaImage::from_pixel(4, 4, Rgba([255, 255, 255, 0]))
+                .save(&src_path)
+                .unwrap();
+
+            let (average, colors) = get_dominant_colors(&src_path, 4).unwrap();
+
+            assert_eq!(average, None);
+            assert!(colors.is_empty());
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod median_cut {
+        use super::*;
+
+        #[test]
+        fn same_colors_in_same_box_when_median_is_repeated() {
+            let pixels = vec![[0, 0, 0], [10, 0, 0], [10, 0, 0], [10, 0, 0]];
+
+            let mut boxes = median_cut(pixels, 2);
+            boxes.sort_by_key(|v| v.len());
+
+            assert_eq!(
+                boxes,
+                vec![vec![[0, 0, 0]], vec![[10, 0, 0], [10, 0, 0], [10, 0, 0]]]
+            );
+        }
+
+        #[test]
+        fn fewer_boxes_when_colors_are_few() {
+            let pixels = vec![[1, 2, 3], [1, 2, 3], [4, 5, 6]];
+
+            assert_eq!(median_cut(pixels, 8).len(), 2);
+        }
+    }
+}
@@ -10,6 +10,11 @@ pub(crate) enum ResizedPngError {
     ParameterError,
     LimitsError,
     InputSizeError,
+    InvalidOption,
+    InvalidSize,
+    InvalidRect,
+    PnaSizeError,
+    InvalidPosition,
 }
 
 impl ResizedPngError {
@@ -23,6 +28,11 @@ impl ResizedPngError {
             Self::ParameterError => 6,
             Self::LimitsError => 7,
             Self::InputSizeError => 8,
+            Self::InvalidOption => 9,
+            Self::InvalidSize => 10,
+            Self::InvalidRect => 11,
+            Self::PnaSizeError => 12,
+            Self::InvalidPosition => 13,
         }
     }
 }
@@ -49,6 +59,28 @@ impl From<image::error::ImageError> for ResizedPngError {
     }
 }
 
+impl From<png::EncodingError> for ResizedPngError {
+    fn from(e: png::EncodingError) -> Self {
+        match e {
+            png::EncodingError::IoError(e) => e.into(),
+            png::EncodingError::Format(_) => Self::EncodingError,
+            png::EncodingError::Parameter(_) => Self::ParameterError,
+            png::EncodingError::LimitsExceeded => Self::LimitsError,
+        }
+    }
+}
+
+impl From<png::DecodingError> for ResizedPngError {
+    fn from(e: png::DecodingError) -> Self {
+        match e {
+            png::DecodingError::IoError(e) => e.into(),
+            png::DecodingError::Format(_) => Self::DecodingError,
+            png::DecodingError::Parameter(_) => Self::ParameterError,
+            png::DecodingError::LimitsExceeded => Self::LimitsError,
+        }
+    }
+}
+
 impl From<fir::ImageBufferError> for ResizedPngError {
     fn from(e: fir::ImageBufferError) -> Self {
         match e {
@@ -0,0 +1,845 @@
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use image::{ColorType, DynamicImage, ImageOutputFormat};
+
+use crate::error::ResizedPngError;
+use crate::metadata::PngMetadata;
+use crate::options::ResizeOptions;
+use crate::quantize::{quantize, IndexedImage};
+
+/// JPEGの品質の既定値
+pub(crate) const DEFAULT_JPEG_QUALITY: u8 = 75;
+
+/// 出力する画像の形式
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum OutputFormat {
+    Png,
+    Bmp,
+    Jpeg,
+    /// 可逆圧縮のWebP
+    WebP,
+    Tga,
+    Ico,
+    Qoi,
+}
+
+impl OutputFormat {
+    /// `GetImageType`と同じ名前から形式を読み取る関数。
+    pub(crate) fn from_name(name: &str) -> Option<OutputFormat> {
+        match name {
+            "PNG" => Some(OutputFormat::Png),
+            "BMP" => Some(OutputFormat::Bmp),
+            "JPEG" => Some(OutputFormat::Jpeg),
+            "WEBP" => Some(OutputFormat::WebP),
+            "TGA" => Some(OutputFormat::Tga),
+            "ICO" => Some(OutputFormat::Ico),
+            "QOI" => Some(OutputFormat::Qoi),
+            _ => None,
+        }
+    }
+
+    /// ファイルの拡張子から形式を読み取る関数。
+    pub(crate) fn from_extension(path: &Path) -> Option<OutputFormat> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+
+        match extension.as_str() {
+            "png" => Some(OutputFormat::Png),
+            "bmp" => Some(OutputFormat::Bmp),
+            "jpg" | "jpeg" => Some(OutputFormat::Jpeg),
+            "webp" => Some(OutputFormat::WebP),
+            "tga" => Some(OutputFormat::Tga),
+            "ico" => Some(OutputFormat::Ico),
+            "qoi" => Some(OutputFormat::Qoi),
+            _ => None,
+        }
+    }
+
+    fn to_image_output_format(self, quality: u8) -> ImageOutputFormat {
+        match self {
+            OutputFormat::Png => ImageOutputFormat::Png,
+            OutputFormat::Bmp => ImageOutputFormat::Bmp,
+            OutputFormat::Jpeg => ImageOutputFormat::Jpeg(quality),
+            OutputFormat::WebP => ImageOutputFormat::WebP,
+            OutputFormat::Tga => ImageOutputFormat::Tga,
+            OutputFormat::Ico => ImageOutputFormat::Ico,
+            OutputFormat::Qoi => ImageOutputFormat::Qoi,
+        }
+    }
+
+    /// 画像をその形式で出力できる色の種類に変換する関数。
+    fn convert_image(self, image: DynamicImage, background: [u8; 4]) -> DynamicImage {
+        let color_type = image.color();
+
+        match self {
+            OutputFormat::Png => image,
+            OutputFormat::Jpeg if color_type.has_alpha() => flatten_alpha(image, background),
+            OutputFormat::Jpeg if color_type.has_color() => {
+                DynamicImage::ImageRgb8(image.to_rgb8())
+            }
+            OutputFormat::Jpeg => DynamicImage::ImageLuma8(image.to_luma8()),
+            OutputFormat::Qoi if color_type.has_alpha() => {
+                DynamicImage::ImageRgba8(image.to_rgba8())
+            }
+            OutputFormat::Qoi => DynamicImage::ImageRgb8(image.to_rgb8()),
+            _ => match (color_type.has_color(), color_type.has_alpha()) {
+                (false, false) => DynamicImage::ImageLuma8(image.to_luma8()),
+                (false, true) => DynamicImage::ImageLumaA8(image.to_luma_alpha8()),
+                (true, false) => DynamicImage::ImageRgb8(image.to_rgb8()),
+                (true, true) => DynamicImage::ImageRgba8(image.to_rgba8()),
+            },
+        }
+    }
+}
+
+/// pngの圧縮率
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub(crate) enum PngCompression {
+    #[default]
+    Fast,
+    Default,
+    Best,
+}
+
+impl PngCompression {
+    pub(crate) fn from_name(name: &str) -> Option<PngCompression> {
+        match name {
+            "Fast" => Some(PngCompression::Fast),
+            "Default" => Some(PngCompression::Default),
+            "Best" => Some(PngCompression::Best),
+            _ => None,
+        }
+    }
+
+    fn to_png_compression(self) -> png::Compression {
+        match self {
+            PngCompression::Fast => png::Compression::Fast,
+            PngCompression::Default => png::Compression::Default,
+            PngCompression::Best => png::Compression::Best,
+        }
+    }
+}
+
+/// pngのフィルタ
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub(crate) enum PngFilter {
+    None,
+    Sub,
+    Up,
+    Avg,
+    Paeth,
+    /// 行ごとに適したフィルタを選ぶ
+    #[default]
+    Adaptive,
+}
+
+impl PngFilter {
+    /// 最適化の際に試すフィルタ
+    const ALL: [PngFilter; 6] = [
+        PngFilter::None,
+        PngFilter::Sub,
+        PngFilter::Up,
+        PngFilter::Avg,
+        PngFilter::Paeth,
+        PngFilter::Adaptive,
+    ];
+
+    pub(crate) fn from_name(name: &str) -> Option<PngFilter> {
+        match name {
+            "None" => Some(PngFilter::None),
+            "Sub" => Some(PngFilter::Sub),
+            "Up" => Some(PngFilter::Up),
+            "Avg" => Some(PngFilter::Avg),
+            "Paeth" => Some(PngFilter::Paeth),
+            "Adaptive" => Some(PngFilter::Adaptive),
+            _ => None,
+        }
+    }
+
+    fn to_png_filter(self) -> (png::FilterType, png::AdaptiveFilterType) {
+        match self {
+            PngFilter::None => (
+                png::FilterType::NoFilter,
+                png::AdaptiveFilterType::NonAdaptive,
+            ),
+            PngFilter::Sub => (png::FilterType::Sub, png::AdaptiveFilterType::NonAdaptive),
+            PngFilter::Up => (png::FilterType::Up, png::AdaptiveFilterType::NonAdaptive),
+            PngFilter::Avg => (png::FilterType::Avg, png::AdaptiveFilterType::NonAdaptive),
+            PngFilter::Paeth => (png::FilterType::Paeth, png::AdaptiveFilterType::NonAdaptive),
+            PngFilter::Adaptive => (png::FilterType::Sub, png::AdaptiveFilterType::Adaptive),
+        }
+    }
+}
+
+/// 画像を指定された形式で出力する関数。
+/// 形式が対応していない色の種類は変換し、JPEGの透明な部分は背景色で塗りつぶす。
+/// 付加情報はpngの場合のみ書き込む。
+pub(crate) fn save_image(
+    image: DynamicImage,
+    dist_path: &Path,
+    format: OutputFormat,
+    options: &ResizeOptions,
+    metadata: &PngMetadata,
+) -> Result<(), ResizedPngError> {
+    let image = format.convert_image(image, options.background);
+
+    if format == OutputFormat::Png {
+        let data = match options.colors {
+            Some(max_colors) => {
+                PngData::from_indexed(&quantize(&image, max_colors, options.dither))
+            }
+            None => PngData::from_image(&image),
+        };
+
+        let buffer = encode_png_with_options(&[data], None, metadata, options)?;
+        std::fs::write(dist_path, buffer)?;
+
+        return Ok(());
+    }
+
+    let mut writer = BufWriter::new(File::create(dist_path)?);
+    image.write_to(&mut writer, format.to_image_output_format(options.quality))?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// 画像をフレームとしてAPNGに出力する関数。
+/// フレームは画像と表示時間(ミリ秒)の組で、すべて同じ大きさと色の種類である必要がある。
+pub(crate) fn save_apng(
+    frames: Vec<(DynamicImage, u16)>,
+    dist_path: &Path,
+    options: &ResizeOptions,
+    metadata: &PngMetadata,
+) -> Result<(), ResizedPngError> {
+    let (frames, delays): (Vec<_>, Vec<_>) = frames
+        .into_iter()
+        .map(|(image, delay)| (PngData::from_image(&image), delay))
+        .unzip();
+
+    let buffer = encode_png_with_options(&frames, Some(&delays), metadata, options)?;
+    std::fs::write(dist_path, buffer)?;
+
+    Ok(())
+}
+
+/// 設定の圧縮率とフィルタでpngにする関数。
+fn encode_png_with_options(
+    frames: &[PngData],
+    delays: Option<&[u16]>,
+    metadata: &PngMetadata,
+    options: &ResizeOptions,
+) -> Result<Vec<u8>, ResizedPngError> {
+    // 最適化する場合は、すべてのフィルタを最高の圧縮率で試して一番小さいものを使う。
+    match options.optimize {
+        true => Ok(PngFilter::ALL
+            .iter()
+            .map(|filter| encode_png(frames, delays, metadata, PngCompression::Best, *filter))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .min_by_key(|v| v.len())
+            .expect("filters are not empty")),
+        false => encode_png(
+            frames,
+            delays,
+            metadata,
+            options.compression,
+            options.png_filter,
+        ),
+    }
+}
+
+/// pngとして書き込む画素のデータ
+struct PngData {
+    width: u32,
+    height: u32,
+    color_type: png::ColorType,
+    bit_depth: png::BitDepth,
+    /// パレットの色(RGB)
+    palette: Option<Vec<u8>>,
+    /// パレットの色の透明度
+    trns: Option<Vec<u8>>,
+    buffer: Vec<u8>,
+}
+
+impl PngData {
+    fn from_image(image: &DynamicImage) -> PngData {
+        let (color_type, bit_depth) = match image.color() {
+            ColorType::L8 => (png::ColorType::Grayscale, png::BitDepth::Eight),
+            ColorType::La8 => (png::ColorType::GrayscaleAlpha, png::BitDepth::Eight),
+            ColorType::Rgb8 => (png::ColorType::Rgb, png::BitDepth::Eight),
+            ColorType::Rgba8 => (png::ColorType::Rgba, png::BitDepth::Eight),
+            ColorType::L16 => (png::ColorType::Grayscale, png::BitDepth::Sixteen),
+            ColorType::La16 => (png::ColorType::GrayscaleAlpha, png::BitDepth::Sixteen),
+            ColorType::Rgb16 => (png::ColorType::Rgb, png::BitDepth::Sixteen),
+            ColorType::Rgba16 => (png::ColorType::Rgba, png::BitDepth::Sixteen),
+            _ => return PngData::from_image(&DynamicImage::ImageRgba16(image.to_rgba16())),
+        };
+
+        // pngの16bitの値はビッグエンディアン。
+        let buffer = match bit_depth {
+            png::BitDepth::Sixteen => image
+                .as_bytes()
+                .chunks_exact(2)
+                .flat_map(|v| u16::from_ne_bytes([v[0], v[1]]).to_be_bytes())
+                .collect(),
+            _ => image.as_bytes().to_vec(),
+        };
+
+        PngData {
+            width: image.width(),
+            height: image.height(),
+            color_type,
+            bit_depth,
+            palette: None,
+            trns: None,
+            buffer,
+        }
+    }
+
+    /// 色数に応じて、1画素あたりのビット数をできるだけ小さくする関数。
+    fn from_indexed(image: &IndexedImage) -> PngData {
+        let bits = match image.palette.len() {
+            0..=2 => 1,
+            3..=4 => 2,
+            5..=16 => 4,
+            _ => 8,
+        };
+        let bit_depth = png::BitDepth::from_u8(bits).expect("bits is valid depth");
+
+        let palette = image
+            .palette
+            .iter()
+            .flat_map(|v| [v[0], v[1], v[2]])
+            .collect();
+        // 透明度を持つ色はパレットの先頭に並んでいるので、その分だけ書き込む。
+        let trns: Vec<u8> = image
+            .palette
+            .iter()
+            .map(|v| v[3])
+            .take_while(|v| *v != 255)
+            .collect();
+
+        let buffer = image
+            .indices
+            .chunks_exact(image.width as usize)
+            .flat_map(|row| pack_row(row, bits))
+            .collect();
+
+        PngData {
+            width: image.width,
+            height: image.height,
+            color_type: png::ColorType::Indexed,
+            bit_depth,
+            palette: Some(palette),
+            trns: (!trns.is_empty()).then_some(trns),
+            buffer,
+        }
+    }
+}
+
+/// 1行分のパレットの番号を、指定されたビット数で詰める関数。
+fn pack_row(row: &[u8], bits: u8) -> Vec<u8> {
+    let per_byte = (8 / bits) as usize;
+
+    row.chunks(per_byte)
+        .map(|chunk| {
+            chunk.iter().enumerate().fold(0, |byte, (i, index)| {
+                byte | (index << (8 - bits as usize * (i + 1)))
+            })
+        })
+        .collect()
+}
+
+/// 画素のデータと付加情報を、指定された圧縮率とフィルタでpngにする関数。
+/// 表示時間があればAPNGにし、最初のフレームを既定の画像とする。
+fn encode_png(
+    frames: &[PngData],
+    delays: Option<&[u16]>,
+    metadata: &PngMetadata,
+    compression: PngCompression,
+    filter: PngFilter,
+) -> Result<Vec<u8>, ResizedPngError> {
+    let mut buffer = Vec::new();
+    let data = &frames[0];
+
+    let mut info = png::Info::with_size(data.width, data.height);
+    info.color_type = data.color_type;
+    info.bit_depth = data.bit_depth;
+    info.palette = data.palette.as_deref().map(Cow::Borrowed);
+    info.trns = data.trns.as_deref().map(Cow::Borrowed);
+    metadata.apply(&mut info);
+
+    let mut encoder = png::Encoder::with_info(&mut buffer, info)?;
+    encoder.set_compression(compression.to_png_compression());
+    let (filter, adaptive_filter) = filter.to_png_filter();
+    encoder.set_filter(filter);
+    encoder.set_adaptive_filter(adaptive_filter);
+    if delays.is_some() {
+        // 繰り返しの回数は0(無限)にする。
+        encoder.set_animated(frames.len() as u32, 0)?;
+    }
+
+    let mut writer = encoder.write_header()?;
+    for (i, data) in frames.iter().enumerate() {
+        if let Some(delays) = delays {
+            writer.set_frame_delay(delays[i], 1000)?;
+        }
+        writer.write_image_data(&data.buffer)?;
+    }
+    writer.finish()?;
+
+    Ok(buffer)
+}
+
+fn flatten_alpha(image: DynamicImage, background: [u8; 4]) -> DynamicImage {
+    let image = image.to_rgba8();
+
+    let buffer = image
+        .pixels()
+        .flat_map(|pixel| {
+            let alpha = pixel.0[3] as u32;
+            [0, 1, 2].map(|i| {
+                let value = pixel.0[i] as u32 * alpha + background[i] as u32 * (255 - alpha);
+                ((value + 127) / 255) as u8
+            })
+        })
+        .collect();
+
+    DynamicImage::ImageRgb8(
+        image::RgbImage::from_raw(image.width(), image.height(), buffer)
+            .expect("buffer size is same"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    use image::{Rgba, RgbaImage};
+
+    mod output_format {
+        use super::*;
+
+        #[test]
+        fn checking_value_of_each_name() {
+            assert_eq!(OutputFormat::from_name("PNG"), Some(OutputFormat::Png));
+            assert_eq!(OutputFormat::from_name("BMP"), Some(OutputFormat::Bmp));
+            assert_eq!(OutputFormat::from_name("JPEG"), Some(OutputFormat::Jpeg));
+            assert_eq!(OutputFormat::from_name("WEBP"), Some(OutputFormat::WebP));
+            assert_eq!(OutputFormat::from_name("TGA"), Some(OutputFormat::Tga));
+            assert_eq!(OutputFormat::from_name("ICO"), Some(OutputFormat::Ico));
+            assert_eq!(OutputFormat::from_name("QOI"), Some(OutputFormat::Qoi));
+            assert_eq!(OutputFormat::from_name("GIF"), None);
+        }
+
+        #[test]
+        fn checking_value_of_each_extension() {
+            let from_extension = |v: &str| OutputFormat::from_extension(&PathBuf::from(v));
+
+            assert_eq!(from_extension("a.png"), Some(OutputFormat::Png));
+            assert_eq!(from_extension("a.BMP"), Some(OutputFormat::Bmp));
+            assert_eq!(from_extension("a.jpg"), Some(OutputFormat::Jpeg));
+            assert_eq!(from_extension("a.jpeg"), Some(OutputFormat::Jpeg));
+            assert_eq!(from_extension("a.webp"), Some(OutputFormat::WebP));
+            assert_eq!(from_extension("a.tga"), Some(OutputFormat::Tga));
+            assert_eq!(from_extension("a.ico"), Some(OutputFormat::Ico));
+            assert_eq!(from_extension("a.qoi"), Some(OutputFormat::Qoi));
+            assert_eq!(from_extension("a.gif"), None);
+            assert_eq!(from_extension("a"), None);
+        }
+    }
+
+    mod png_compression {
+        use super::*;
+
+        #[test]
+        fn checking_value_of_each_name() {
+            assert_eq!(
+                PngCompression::from_name("Fast"),
+                Some(PngCompression::Fast)
+            );
+            assert_eq!(
+                PngCompression::from_name("Default"),
+                Some(PngCompression::Default)
+            );
+            assert_eq!(
+                PngCompression::from_name("Best"),
+                Some(PngCompression::Best)
+            );
+            assert_eq!(PngCompression::from_name("Huffman"), None);
+        }
+    }
+
+    mod png_filter {
+        use super::*;
+
+        #[test]
+        fn checking_value_of_each_name() {
+            assert_eq!(PngFilter::from_name("None"), Some(PngFilter::None));
+            assert_eq!(PngFilter::from_name("Sub"), Some(PngFilter::Sub));
+            assert_eq!(PngFilter::from_name("Up"), Some(PngFilter::Up));
+            assert_eq!(PngFilter::from_name("Avg"), Some(PngFilter::Avg));
+            assert_eq!(PngFilter::from_name("Paeth"), Some(PngFilter::Paeth));
+            assert_eq!(PngFilter::from_name("Adaptive"), Some(PngFilter::Adaptive));
+            assert_eq!(PngFilter::from_name("Average"), None);
+        }
+    }
+
+    mod save_image {
+        use super::*;
+
+        use tempfile::tempdir;
+
+        #[test]
+        fn same_format_when_each_format_is_specified() {
+            let out_dir = tempdir().unwrap();
+
+            let image =
+                DynamicImage::ImageRgba8(RgbaImage::from_pixel(16, 16, Rgba([255, 0, 0, 128])));
+            let cases = [
+                ("png", OutputFormat::Png, image::ImageFormat::Png),
+                ("bmp", OutputFormat::Bmp, image::ImageFormat::Bmp),
+                ("jpg", OutputFormat::Jpeg, image::ImageFormat::Jpeg),
+                ("webp", OutputFormat::WebP, image::ImageFormat::WebP),
+                ("tga", OutputFormat::Tga, image::ImageFormat::Tga),
+                ("ico", OutputFormat::Ico, image::ImageFormat::Ico),
+                ("qoi", OutputFormat::Qoi, image::ImageFormat::Qoi),
+            ];
+
+            for (extension, format, expected) in cases {
+                let dist_path = out_dir.path().join(format!("output.{}", extension));
+
+                save_image(
+                    image.clone(),
+                    &dist_path,
+                    format,
+                    &ResizeOptions::default(),
+                    &PngMetadata::default(),
+                )
+                .unwrap();
+
+                let output = image::io::Reader::open(&dist_path)
+                    .unwrap()
+                    .with_guessed_format()
+                    .unwrap();
+                assert_eq!(output.format(), Some(expected));
+                assert_eq!(output.decode().unwrap().width(), 16);
+            }
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn same_pixels_when_format_is_lossless() {
+            let out_dir = tempdir().unwrap();
+
+            let image = DynamicImage::ImageRgba8(RgbaImage::from_fn(4, 4, |x, y| {
+                Rgba([x as u8 * 60, y as u8 * 60, 10, 255 - x as u8 * 10])
+            }));
+
+            for (extension, format) in [
+                ("webp", OutputFormat::WebP),
+                ("qoi", OutputFormat::Qoi),
+                ("tga", OutputFormat::Tga),
+            ] {
+                let dist_path = out_dir.path().join(format!("output.{}", extension));
+
+                save_image(
+                    image.clone(),
+                    &dist_path,
+                    format,
+                    &ResizeOptions::default(),
+                    &PngMetadata::default(),
+                )
+                .unwrap();
+
+                let output = image::io::Reader::open(&dist_path)
+                    .unwrap()
+                    .with_guessed_format()
+                    .unwrap()
+                    .decode()
+                    .unwrap();
+                assert_eq!(output.to_rgba8(), image.to_rgba8(), "{:?}", format);
+            }
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn background_color_when_format_is_jpeg() {
+            let out_dir = tempdir().unwrap();
+
+            let dist_path = out_dir.path().join("output.jpg");
+            let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(8, 8, Rgba([0, 0, 0, 0])));
+
+            let options = ResizeOptions {
+                quality: 100,
+                background: [255, 255, 255, 0],
+                ..Default::default()
+            };
+
+            save_image(
+                image,
+                &dist_path,
+                OutputFormat::Jpeg,
+                &options,
+                &PngMetadata::default(),
+            )
+            .unwrap();
+
+            let output = image::open(&dist_path).unwrap().to_rgb8();
+            assert!(output.pixels().all(|p| p.0.iter().all(|v| *v >= 250)));
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn failed_when_ico_is_too_large() {
+            let out_dir = tempdir().unwrap();
+
+            let dist_path = out_dir.path().join("output.ico");
+            let image = DynamicImage::new_rgba8(257, 1);
+
+            assert!(save_image(
+                image,
+                &dist_path,
+                OutputFormat::Ico,
+                &ResizeOptions::default(),
+                &PngMetadata::default()
+            )
+            .is_err());
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod save_image_as_png {
+        use super::*;
+
+        use tempfile::tempdir;
+
+        fn sample_image() -> DynamicImage {
+            image::open(
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.png"),
+            )
+            .unwrap()
+        }
+
+        fn saved_size(dir: &Path, options: &ResizeOptions) -> u64 {
+            let dist_path = dir.join("output.png");
+
+            save_image(
+                sample_image(),
+                &dist_path,
+                OutputFormat::Png,
+                options,
+                &PngMetadata::default(),
+            )
+            .unwrap();
+
+            let output = image::open(&dist_path).unwrap();
+            assert_eq!(output, sample_image());
+
+            std::fs::metadata(&dist_path).unwrap().len()
+        }
+
+        #[test]
+        fn same_pixels_when_each_filter_is_specified() {
+            let out_dir = tempdir().unwrap();
+
+            for png_filter in PngFilter::ALL {
+                let options = ResizeOptions {
+                    compression: PngCompression::Default,
+                    png_filter,
+                    ..Default::default()
+                };
+
+                saved_size(out_dir.path(), &options);
+            }
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn smaller_size_when_compression_is_best() {
+            let out_dir = tempdir().unwrap();
+
+            let fast = saved_size(out_dir.path(), &ResizeOptions::default());
+            let best = saved_size(
+                out_dir.path(),
+                &ResizeOptions {
+                    compression: PngCompression::Best,
+                    ..Default::default()
+                },
+            );
+
+            assert!(best < fast);
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn smallest_size_when_optimize_is_enabled() {
+            let out_dir = tempdir().unwrap();
+
+            let optimized = saved_size(
+                out_dir.path(),
+                &ResizeOptions {
+                    optimize: true,
+                    ..Default::default()
+                },
+            );
+
+            for png_filter in PngFilter::ALL {
+                let options = ResizeOptions {
+                    compression: PngCompression::Best,
+                    png_filter,
+                    ..Default::default()
+                };
+
+                assert!(optimized <= saved_size(out_dir.path(), &options));
+            }
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod save_image_with_colors {
+        use super::*;
+
+        use std::collections::HashSet;
+
+        use tempfile::tempdir;
+
+        fn gradation_image() -> DynamicImage {
+            DynamicImage::ImageRgba8(RgbaImage::from_fn(64, 64, |x, y| {
+                Rgba([(x * 4) as u8, (y * 4) as u8, 128, (x * 4) as u8])
+            }))
+        }
+
+        #[test]
+        fn at_most_colors_when_colors_is_specified() {
+            let out_dir = tempdir().unwrap();
+
+            for colors in [2, 4, 16, 256] {
+                for dither in [false, true] {
+                    let dist_path = out_dir.path().join("output.png");
+                    let options = ResizeOptions {
+                        colors: Some(colors),
+                        dither,
+                        ..Default::default()
+                    };
+
+                    save_image(
+                        gradation_image(),
+                        &dist_path,
+                        OutputFormat::Png,
+                        &options,
+                        &PngMetadata::default(),
+                    )
+                    .unwrap();
+
+                    let output = image::open(&dist_path).unwrap().to_rgba8();
+                    let unique: HashSet<[u8; 4]> = output.pixels().map(|p| p.0).collect();
+                    assert_eq!(output.dimensions(), (64, 64));
+                    assert!(unique.len() <= colors as usize);
+                }
+            }
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn indexed_png_with_trns_when_colors_is_specified() {
+            let out_dir = tempdir().unwrap();
+
+            let dist_path = out_dir.path().join("output.png");
+            let options = ResizeOptions {
+                colors: Some(16),
+                ..Default::default()
+            };
+
+            save_image(
+                gradation_image(),
+                &dist_path,
+                OutputFormat::Png,
+                &options,
+                &PngMetadata::default(),
+            )
+            .unwrap();
+
+            let decoder = png::Decoder::new(File::open(&dist_path).unwrap());
+            let reader = decoder.read_info().unwrap();
+            let info = reader.info();
+            assert_eq!(info.color_type, png::ColorType::Indexed);
+            assert_eq!(info.bit_depth, png::BitDepth::Four);
+            assert!(info.trns.is_some());
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn same_pixels_when_image_has_few_colors() {
+            let out_dir = tempdir().unwrap();
+
+            let dist_path = out_dir.path().join("output.png");
+            let image = DynamicImage::ImageRgba8(RgbaImage::from_fn(5, 3, |x, y| {
+                Rgba([(x % 3 * 100) as u8, 0, (y * 100) as u8, 255])
+            }));
+            let options = ResizeOptions {
+                colors: Some(256),
+                ..Default::default()
+            };
+
+            save_image(
+                image.clone(),
+                &dist_path,
+                OutputFormat::Png,
+                &options,
+                &PngMetadata::default(),
+            )
+            .unwrap();
+
+            let output = image::open(&dist_path).unwrap();
+            assert_eq!(output.to_rgba8(), image.to_rgba8());
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod pack_row {
+        use super::*;
+
+        #[test]
+        fn packed_bytes_of_each_bits() {
+            assert_eq!(
+                pack_row(&[1, 0, 1, 1, 0, 0, 0, 1, 1], 1),
+                [0b10110001, 0b10000000]
+            );
+            assert_eq!(pack_row(&[3, 1, 2], 2), [0b11011000]);
+            assert_eq!(pack_row(&[15, 1, 10], 4), [0xF1, 0xA0]);
+            assert_eq!(pack_row(&[200, 7], 8), [200, 7]);
+        }
+    }
+
+    mod flatten_alpha {
+        use super::*;
+
+        #[test]
+        fn blended_color_when_pixel_is_semi_transparent() {
+            let image =
+                DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba([255, 0, 0, 128])));
+
+            let output = flatten_alpha(image, [0, 0, 255, 0]).to_rgb8();
+
+            assert_eq!(output.get_pixel(0, 0).0, [128, 0, 127]);
+        }
+    }
+}
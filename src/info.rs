@@ -0,0 +1,186 @@
+use std::{fs::File, io::BufReader, path::Path};
+
+use image::codecs::{
+    bmp::BmpDecoder, gif::GifDecoder, ico::IcoDecoder, jpeg::JpegDecoder, png::PngDecoder,
+    qoi::QoiDecoder, tga::TgaDecoder, tiff::TiffDecoder, webp::WebPDecoder,
+};
+use image::io::Reader as ImageReader;
+use image::{ColorType, ImageDecoder, ImageFormat};
+
+use crate::animation::frame_count;
+use crate::color::color_type_name;
+use crate::error::ResizedPngError;
+
+/// 画像の大きさ(横幅、縦幅)と色の種類
+type Header = ((u32, u32), ColorType);
+
+/// 画像の情報
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct ImageInfo {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) color_type: ColorType,
+    pub(crate) frame_count: usize,
+}
+
+impl ImageInfo {
+    /// 画像の情報を読み取る関数。
+    /// ヘッダだけで分かる形式は、画像全体を読み込まない。
+    pub(crate) fn read(src_path: &Path) -> Result<ImageInfo, ResizedPngError> {
+        let format = ImageReader::open(src_path)
+            .and_then(|v| v.with_guessed_format())?
+            .format();
+
+        let ((width, height), color_type) = match read_header(src_path, format)? {
+            Some(v) => v,
+            None => {
+                let image = ImageReader::open(src_path)
+                    .and_then(|v| v.with_guessed_format())?
+                    .decode()?;
+                ((image.width(), image.height()), image.color())
+            }
+        };
+
+        Ok(ImageInfo {
+            width,
+            height,
+            color_type,
+            frame_count: frame_count(src_path)?,
+        })
+    }
+
+    /// 1チャンネルあたりのビット数を返す関数。
+    pub(crate) fn bit_depth(&self) -> u16 {
+        self.color_type.bits_per_pixel() / self.color_type.channel_count() as u16
+    }
+
+    /// `SaoriResponse`のValueとして返す値を返す関数。
+    /// 横幅、縦幅、色の種類、ビット数、透明度の有無、フレームの数の順。
+    pub(crate) fn to_values(&self) -> Vec<String> {
+        vec![
+            self.width.to_string(),
+            self.height.to_string(),
+            color_type_name(self.color_type).to_string(),
+            self.bit_depth().to_string(),
+            (self.color_type.has_alpha() as u8).to_string(),
+            self.frame_count.to_string(),
+        ]
+    }
+}
+
+/// 画像の横幅と縦幅を、ヘッダから読み取る関数。
+pub(crate) fn image_size(src_path: &Path) -> Result<(u32, u32), ResizedPngError> {
+    let reader = ImageReader::open(src_path).and_then(|v| v.with_guessed_format())?;
+
+    Ok(reader.into_dimensions()?)
+}
+
+/// 画像の大きさと色の種類を、ヘッダから読み取る関数。
+/// ヘッダを読み取れない形式の場合は`None`を返す。
+fn read_header(
+    src_path: &Path,
+    format: Option<ImageFormat>,
+) -> Result<Option<Header>, ResizedPngError> {
+    let reader = BufReader::new(File::open(src_path)?);
+
+    let header = match format {
+        Some(ImageFormat::Png) => header_of(PngDecoder::new(reader)?),
+        Some(ImageFormat::Jpeg) => header_of(JpegDecoder::new(reader)?),
+        Some(ImageFormat::Gif) => header_of(GifDecoder::new(reader)?),
+        Some(ImageFormat::WebP) => header_of(WebPDecoder::new(reader)?),
+        Some(ImageFormat::Bmp) => header_of(BmpDecoder::new(reader)?),
+        Some(ImageFormat::Ico) => header_of(IcoDecoder::new(reader)?),
+        Some(ImageFormat::Tga) => header_of(TgaDecoder::new(reader)?),
+        Some(ImageFormat::Tiff) => header_of(TiffDecoder::new(reader)?),
+        Some(ImageFormat::Qoi) => header_of(QoiDecoder::new(reader)?),
+        _ => return Ok(None),
+    };
+
+    Ok(Some(header))
+}
+
+fn header_of<'a>(decoder: impl ImageDecoder<'a>) -> Header {
+    (decoder.dimensions(), decoder.color_type())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    mod image_info {
+        use super::*;
+
+        #[test]
+        fn checking_value_when_image_is_png() {
+            let path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.png");
+
+            let info = ImageInfo::read(&path).unwrap();
+
+            assert_eq!((info.width, info.height), image_size(&path).unwrap());
+            assert_eq!(info.color_type, image::open(&path).unwrap().color());
+            assert_eq!(info.frame_count, 1);
+        }
+
+        #[test]
+        fn same_value_as_decoded_image_when_each_format() {
+            for name in [
+                "sample.jpg",
+                "sample.bmp",
+                "sample.gif",
+                "sample.webp",
+                "sample.tif",
+                "sample.ico",
+            ] {
+                let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                    .join("test_target/image")
+                    .join(name);
+                let image = image::open(&path).unwrap();
+
+                let info = ImageInfo::read(&path).unwrap();
+
+                assert_eq!((info.width, info.height), (image.width(), image.height()));
+                assert_eq!(info.color_type, image.color());
+            }
+        }
+
+        #[test]
+        fn checking_values_of_each_field() {
+            let info = ImageInfo {
+                width: 120,
+                height: 80,
+                color_type: ColorType::La16,
+                frame_count: 3,
+            };
+
+            assert_eq!(info.to_values(), vec!["120", "80", "La16", "16", "1", "3"]);
+        }
+
+        #[test]
+        fn failed_when_file_does_not_exist() {
+            let path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/not_exist.png");
+
+            assert!(matches!(
+                ImageInfo::read(&path),
+                Err(ResizedPngError::NotFound)
+            ));
+        }
+    }
+
+    mod image_size {
+        use super::*;
+
+        #[test]
+        fn failed_when_file_is_not_image() {
+            let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+
+            assert!(matches!(
+                image_size(&path),
+                Err(ResizedPngError::Unsupported)
+            ));
+        }
+    }
+}
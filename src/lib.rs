@@ -1,9 +1,23 @@
+mod animation;
+mod bounds;
 mod chars;
+mod color;
+mod composite;
+mod dominant;
 mod error;
+mod format;
+mod info;
+mod metadata;
+mod options;
+mod orientation;
+mod pixel;
+mod pna;
 mod procedure;
+mod quantize;
 mod request;
 mod resized_png;
 mod response;
+mod text;
 
 use winapi::ctypes::c_long;
 use winapi::shared::minwindef::{BOOL, DWORD, HGLOBAL, HINSTANCE, LPVOID, MAX_PATH, TRUE};
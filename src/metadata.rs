@@ -0,0 +1,172 @@
+use std::{borrow::Cow, fs::File, io::BufReader, path::Path};
+
+use png::text_metadata::{ITXtChunk, TEXtChunk, ZTXtChunk};
+
+/// 入力されたpngの付加情報の扱い
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub(crate) enum MetadataMode {
+    /// 付加情報を書き込まない
+    #[default]
+    Strip,
+    /// 入力されたpngの付加情報を書き込む
+    Keep,
+}
+
+impl MetadataMode {
+    pub(crate) fn from_name(name: &str) -> Option<MetadataMode> {
+        match name {
+            "Strip" => Some(MetadataMode::Strip),
+            "Keep" => Some(MetadataMode::Keep),
+            _ => None,
+        }
+    }
+}
+
+/// pngに書き込む付加情報
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PngMetadata {
+    /// iCCP
+    icc_profile: Option<Vec<u8>>,
+    /// sRGB
+    srgb: Option<png::SrgbRenderingIntent>,
+    /// gAMA
+    gamma: Option<png::ScaledFloat>,
+    /// pHYs
+    pixel_dims: Option<png::PixelDimensions>,
+    /// tEXt
+    latin1_texts: Vec<TEXtChunk>,
+    /// zTXt
+    compressed_latin1_texts: Vec<ZTXtChunk>,
+    /// iTXt
+    utf8_texts: Vec<ITXtChunk>,
+}
+
+impl PngMetadata {
+    /// pngの付加情報を読み取る関数。
+    /// pngでない場合や読み取れない場合は、空の付加情報を返す。
+    pub(crate) fn read(src_path: &Path) -> PngMetadata {
+        let Ok(file) = File::open(src_path) else {
+            return PngMetadata::default();
+        };
+        let Ok(reader) = png::Decoder::new(BufReader::new(file)).read_info() else {
+            return PngMetadata::default();
+        };
+        let info = reader.info();
+
+        PngMetadata {
+            icc_profile: info.icc_profile.as_ref().map(|v| v.to_vec()),
+            srgb: info.srgb,
+            gamma: info.gama_chunk,
+            pixel_dims: info.pixel_dims,
+            latin1_texts: info.uncompressed_latin1_text.clone(),
+            compressed_latin1_texts: info.compressed_latin1_text.clone(),
+            utf8_texts: info.utf8_text.clone(),
+        }
+    }
+
+    /// 文字列の付加情報を加える関数。
+    /// Latin-1で表せない文字列はiTXt(UTF-8)にする。
+    pub(crate) fn add_text(&mut self, keyword: &str, text: &str) {
+        if text.chars().all(is_latin1) {
+            self.latin1_texts.push(TEXtChunk::new(keyword, text));
+        } else {
+            self.utf8_texts.push(ITXtChunk::new(keyword, text));
+        }
+    }
+
+    /// 付加情報をpngの情報に設定する関数。
+    pub(crate) fn apply<'a>(&'a self, info: &mut png::Info<'a>) {
+        info.icc_profile = self.icc_profile.as_deref().map(Cow::Borrowed);
+        info.srgb = self.srgb;
+        info.source_gamma = self.gamma;
+        info.pixel_dims = self.pixel_dims;
+        info.uncompressed_latin1_text = self.latin1_texts.clone();
+        info.compressed_latin1_text = self.compressed_latin1_texts.clone();
+        info.utf8_text = self.utf8_texts.clone();
+    }
+}
+
+/// pngの文字列の付加情報のキーワードとして使えるかどうかを返す関数。
+/// 1から79文字のLatin-1の文字で、前後に空白がないものが使える。
+pub(crate) fn is_valid_keyword(keyword: &str) -> bool {
+    (1..=79).contains(&keyword.chars().count())
+        && keyword.chars().all(|c| is_latin1(c) && c != '\n')
+        && !keyword.starts_with(' ')
+        && !keyword.ends_with(' ')
+}
+
+fn is_latin1(c: char) -> bool {
+    matches!(c, '\n' | ' '..='~' | '\u{a1}'..='\u{ff}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod metadata_mode {
+        use super::*;
+
+        #[test]
+        fn checking_value_of_each_name() {
+            assert_eq!(MetadataMode::from_name("Strip"), Some(MetadataMode::Strip));
+            assert_eq!(MetadataMode::from_name("Keep"), Some(MetadataMode::Keep));
+            assert_eq!(MetadataMode::from_name("Copy"), None);
+        }
+    }
+
+    mod png_metadata {
+        use super::*;
+
+        use std::path::PathBuf;
+
+        #[test]
+        fn empty_when_image_is_not_png() {
+            let path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.bmp");
+
+            let metadata = PngMetadata::read(&path);
+
+            assert!(metadata.icc_profile.is_none());
+            assert!(metadata.pixel_dims.is_none());
+            assert!(metadata.latin1_texts.is_empty());
+        }
+
+        #[test]
+        fn utf8_text_when_text_is_not_latin1() {
+            let mut metadata = PngMetadata::default();
+
+            metadata.add_text("Author", "tukinami");
+            metadata.add_text("Title", "ゴースト");
+
+            assert_eq!(
+                metadata.latin1_texts,
+                vec![TEXtChunk::new("Author", "tukinami")]
+            );
+            assert_eq!(
+                metadata.utf8_texts,
+                vec![ITXtChunk::new("Title", "ゴースト")]
+            );
+        }
+    }
+
+    mod is_valid_keyword {
+        use super::*;
+
+        #[test]
+        fn true_when_keyword_is_valid() {
+            assert!(is_valid_keyword("Author"));
+            assert!(is_valid_keyword("Ghost Name"));
+            assert!(is_valid_keyword(&"a".repeat(79)));
+        }
+
+        #[test]
+        fn false_when_keyword_is_invalid() {
+            assert!(!is_valid_keyword(""));
+            assert!(!is_valid_keyword(&"a".repeat(80)));
+            assert!(!is_valid_keyword(" Author"));
+            assert!(!is_valid_keyword("Author "));
+            assert!(!is_valid_keyword("作者"));
+            assert!(!is_valid_keyword("Au\nthor"));
+        }
+    }
+}
@@ -0,0 +1,758 @@
+use fast_image_resize as fir;
+
+use crate::animation::AnimationMode;
+use crate::color::color_type_from_name;
+use crate::error::ResizedPngError;
+use crate::format::{OutputFormat, PngCompression, PngFilter, DEFAULT_JPEG_QUALITY};
+use crate::metadata::{is_valid_keyword, MetadataMode};
+use crate::orientation::{Flip, Rotation};
+use crate::resized_png::Rect;
+
+/// 拡大縮小に使うフィルタ
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub(crate) enum ResizeFilter {
+    Nearest,
+    Box,
+    Bilinear,
+    Hamming,
+    CatmullRom,
+    Mitchell,
+    #[default]
+    Lanczos3,
+    SuperSampling,
+}
+
+impl ResizeFilter {
+    pub(crate) fn from_name(name: &str) -> Option<ResizeFilter> {
+        match name {
+            "Nearest" => Some(ResizeFilter::Nearest),
+            "Box" => Some(ResizeFilter::Box),
+            "Bilinear" => Some(ResizeFilter::Bilinear),
+            "Hamming" => Some(ResizeFilter::Hamming),
+            "CatmullRom" => Some(ResizeFilter::CatmullRom),
+            "Mitchell" => Some(ResizeFilter::Mitchell),
+            "Lanczos3" => Some(ResizeFilter::Lanczos3),
+            "SuperSampling" => Some(ResizeFilter::SuperSampling),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn to_resize_alg(self) -> fir::ResizeAlg {
+        match self {
+            ResizeFilter::Nearest => fir::ResizeAlg::Nearest,
+            ResizeFilter::Box => fir::ResizeAlg::Convolution(fir::FilterType::Box),
+            ResizeFilter::Bilinear => fir::ResizeAlg::Convolution(fir::FilterType::Bilinear),
+            ResizeFilter::Hamming => fir::ResizeAlg::Convolution(fir::FilterType::Hamming),
+            ResizeFilter::CatmullRom => fir::ResizeAlg::Convolution(fir::FilterType::CatmullRom),
+            ResizeFilter::Mitchell => fir::ResizeAlg::Convolution(fir::FilterType::Mitchell),
+            ResizeFilter::Lanczos3 => fir::ResizeAlg::Convolution(fir::FilterType::Lanczos3),
+            ResizeFilter::SuperSampling => {
+                fir::ResizeAlg::SuperSampling(fir::FilterType::Lanczos3, 2)
+            }
+        }
+    }
+}
+
+/// 指定された大きさへの合わせ方
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub(crate) enum ResizeMode {
+    /// 縦横比を無視して指定された大きさにする
+    #[default]
+    Stretch,
+    /// 縦横比を保って指定された大きさの内側に収める
+    Fit,
+    /// 縦横比を保って指定された大きさを覆い、はみ出した部分を中央基準で切り取る
+    Cover,
+    /// 縦横比を保って指定された大きさの内側に収め、余白を背景色で埋める
+    Contain,
+}
+
+impl ResizeMode {
+    pub(crate) fn from_name(name: &str) -> Option<ResizeMode> {
+        match name {
+            "Stretch" => Some(ResizeMode::Stretch),
+            "Fit" => Some(ResizeMode::Fit),
+            "Cover" => Some(ResizeMode::Cover),
+            "Contain" => Some(ResizeMode::Contain),
+            _ => None,
+        }
+    }
+}
+
+/// ToResizedPngの追加の設定
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct ResizeOptions {
+    pub(crate) filter: ResizeFilter,
+    pub(crate) mode: ResizeMode,
+    /// `Contain`の余白の色(RGBA)
+    pub(crate) background: [u8; 4],
+    /// 入力された画像より大きくしない
+    pub(crate) shrink_only: bool,
+    /// リニアな色空間で拡大縮小する
+    pub(crate) linear_light: bool,
+    /// アルファ値を乗算せずに拡大縮小する
+    pub(crate) straight_alpha: bool,
+    /// EXIFのOrientationタグに従って向きを直す
+    pub(crate) exif_orientation: bool,
+    /// 拡大縮小の前に行う回転
+    pub(crate) rotation: Rotation,
+    /// 拡大縮小の前に行う反転
+    pub(crate) flip: Flip,
+    /// 拡大縮小の前に切り取る範囲
+    pub(crate) crop_before: Option<Rect>,
+    /// 拡大縮小の後に切り取る範囲
+    pub(crate) crop_after: Option<Rect>,
+    /// 拡大縮小の前に透明な余白を切り取り、この幅の余白を残す
+    pub(crate) trim: Option<u32>,
+    /// 余白を切り取るときに、透明として扱う透明度の最大値
+    pub(crate) trim_threshold: u8,
+    /// 入力された画像と対になるPNAがあれば、アルファ値として読み込む
+    pub(crate) read_pna: bool,
+    /// 左上の画素の色を透明色として扱う
+    pub(crate) color_key: bool,
+    /// RGBのPNGとアルファ値のPNAに分けて出力する
+    pub(crate) pna: bool,
+    /// 出力する色の種類。指定がなければ入力された画像の色の種類を保つ
+    pub(crate) color_type: Option<image::ColorType>,
+    /// 出力する形式。指定がなければ出力するファイルの拡張子から決める
+    pub(crate) format: Option<OutputFormat>,
+    /// JPEGの品質(1から100)
+    pub(crate) quality: u8,
+    /// pngの圧縮率
+    pub(crate) compression: PngCompression,
+    /// pngのフィルタ
+    pub(crate) png_filter: PngFilter,
+    /// pngのフィルタをすべて試し、一番小さくなるものを使う
+    pub(crate) optimize: bool,
+    /// pngをこの色数(2から256)以下のパレットに減色する
+    pub(crate) colors: Option<u16>,
+    /// 減色するときに誤差拡散を行う
+    pub(crate) dither: bool,
+    /// 入力されたpngの付加情報の扱い
+    pub(crate) metadata: MetadataMode,
+    /// pngに書き込む文字列の付加情報(キーワードと文字列)
+    pub(crate) texts: Vec<(String, String)>,
+    /// アニメーションする画像の扱い
+    pub(crate) animation: AnimationMode,
+    /// アニメーションする画像から取り出すフレームの番号(0から)
+    pub(crate) frame: Option<usize>,
+}
+
+impl Default for ResizeOptions {
+    fn default() -> Self {
+        ResizeOptions {
+            filter: ResizeFilter::default(),
+            mode: ResizeMode::default(),
+            background: [0, 0, 0, 0],
+            shrink_only: false,
+            linear_light: false,
+            straight_alpha: false,
+            exif_orientation: true,
+            rotation: Rotation::default(),
+            flip: Flip::default(),
+            crop_before: None,
+            crop_after: None,
+            trim: None,
+            trim_threshold: 0,
+            read_pna: true,
+            color_key: false,
+            pna: false,
+            color_type: None,
+            format: None,
+            quality: DEFAULT_JPEG_QUALITY,
+            compression: PngCompression::default(),
+            png_filter: PngFilter::default(),
+            optimize: false,
+            colors: None,
+            dither: false,
+            metadata: MetadataMode::default(),
+            texts: Vec::new(),
+            animation: AnimationMode::default(),
+            frame: None,
+        }
+    }
+}
+
+impl ResizeOptions {
+    /// `名前=値`形式の引数の並びから設定を読み取る関数。
+    pub(crate) fn from_args(args: &[String]) -> Result<ResizeOptions, ResizedPngError> {
+        let mut options = ResizeOptions::default();
+
+        for arg in args {
+            // 空の引数は指定なしとして扱う。
+            if arg.is_empty() {
+                continue;
+            }
+
+            let (name, value) = arg.split_once('=').ok_or(ResizedPngError::InvalidOption)?;
+
+            match name {
+                "Filter" => {
+                    options.filter =
+                        ResizeFilter::from_name(value).ok_or(ResizedPngError::InvalidOption)?;
+                }
+                "Mode" => {
+                    options.mode =
+                        ResizeMode::from_name(value).ok_or(ResizedPngError::InvalidOption)?;
+                }
+                "Background" => {
+                    options.background =
+                        parse_color(value).ok_or(ResizedPngError::InvalidOption)?;
+                }
+                "ShrinkOnly" => {
+                    options.shrink_only =
+                        parse_bool(value).ok_or(ResizedPngError::InvalidOption)?;
+                }
+                "LinearLight" => {
+                    options.linear_light =
+                        parse_bool(value).ok_or(ResizedPngError::InvalidOption)?;
+                }
+                "StraightAlpha" => {
+                    options.straight_alpha =
+                        parse_bool(value).ok_or(ResizedPngError::InvalidOption)?;
+                }
+                "ExifOrientation" => {
+                    options.exif_orientation =
+                        parse_bool(value).ok_or(ResizedPngError::InvalidOption)?;
+                }
+                "Rotate" => {
+                    options.rotation =
+                        Rotation::from_degrees(value).ok_or(ResizedPngError::InvalidOption)?;
+                }
+                "Flip" => {
+                    options.flip = Flip::from_name(value).ok_or(ResizedPngError::InvalidOption)?;
+                }
+                "CropBefore" => {
+                    options.crop_before = Some(value.parse::<Rect>()?);
+                }
+                "CropAfter" => {
+                    options.crop_after = Some(value.parse::<Rect>()?);
+                }
+                "Trim" => {
+                    options.trim = Some(
+                        value
+                            .parse::<u32>()
+                            .map_err(|_| ResizedPngError::InvalidOption)?,
+                    );
+                }
+                "TrimThreshold" => {
+                    options.trim_threshold = value
+                        .parse::<u8>()
+                        .map_err(|_| ResizedPngError::InvalidOption)?;
+                }
+                "ReadPna" => {
+                    options.read_pna = parse_bool(value).ok_or(ResizedPngError::InvalidOption)?;
+                }
+                "ColorKey" => {
+                    options.color_key = parse_bool(value).ok_or(ResizedPngError::InvalidOption)?;
+                }
+                "Pna" => {
+                    options.pna = parse_bool(value).ok_or(ResizedPngError::InvalidOption)?;
+                }
+                "ColorType" => {
+                    options.color_type =
+                        Some(color_type_from_name(value).ok_or(ResizedPngError::InvalidOption)?);
+                }
+                "Format" => {
+                    options.format =
+                        Some(OutputFormat::from_name(value).ok_or(ResizedPngError::InvalidOption)?);
+                }
+                "Quality" => {
+                    options.quality = value
+                        .parse::<u8>()
+                        .ok()
+                        .filter(|v| (1..=100).contains(v))
+                        .ok_or(ResizedPngError::InvalidOption)?;
+                }
+                "Compression" => {
+                    options.compression =
+                        PngCompression::from_name(value).ok_or(ResizedPngError::InvalidOption)?;
+                }
+                "PngFilter" => {
+                    options.png_filter =
+                        PngFilter::from_name(value).ok_or(ResizedPngError::InvalidOption)?;
+                }
+                "Optimize" => {
+                    options.optimize = parse_bool(value).ok_or(ResizedPngError::InvalidOption)?;
+                }
+                "Colors" => {
+                    options.colors = Some(
+                        value
+                            .parse::<u16>()
+                            .ok()
+                            .filter(|v| (2..=256).contains(v))
+                            .ok_or(ResizedPngError::InvalidOption)?,
+                    );
+                }
+                "Dither" => {
+                    options.dither = parse_bool(value).ok_or(ResizedPngError::InvalidOption)?;
+                }
+                "Metadata" => {
+                    options.metadata =
+                        MetadataMode::from_name(value).ok_or(ResizedPngError::InvalidOption)?;
+                }
+                "Text" => {
+                    let (keyword, text) = value
+                        .split_once(':')
+                        .filter(|(keyword, _)| is_valid_keyword(keyword))
+                        .ok_or(ResizedPngError::InvalidOption)?;
+                    options.texts.push((keyword.to_string(), text.to_string()));
+                }
+                "Animation" => {
+                    options.animation =
+                        AnimationMode::from_name(value).ok_or(ResizedPngError::InvalidOption)?;
+                }
+                "Frame" => {
+                    options.frame = Some(
+                        value
+                            .parse::<usize>()
+                            .map_err(|_| ResizedPngError::InvalidOption)?,
+                    );
+                }
+                _ => return Err(ResizedPngError::InvalidOption),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// `1`/`0`または`true`/`false`形式の真偽値を読み取る関数。
+pub(crate) fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "1" | "true" => Some(true),
+        "0" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// `#RRGGBB`または`#RRGGBBAA`形式の色を読み取る関数。
+pub(crate) fn parse_color(value: &str) -> Option<[u8; 4]> {
+    let hex = value.strip_prefix('#')?;
+    if !hex.is_ascii() {
+        return None;
+    }
+
+    let component = |index: usize| u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).ok();
+
+    match hex.len() {
+        6 => Some([component(0)?, component(1)?, component(2)?, 255]),
+        8 => Some([component(0)?, component(1)?, component(2)?, component(3)?]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod resize_filter {
+        use super::*;
+
+        #[test]
+        fn checking_algorithm_of_each_name() {
+            let alg = |name| ResizeFilter::from_name(name).unwrap().to_resize_alg();
+
+            assert!(matches!(alg("Nearest"), fir::ResizeAlg::Nearest));
+            assert!(matches!(
+                alg("Box"),
+                fir::ResizeAlg::Convolution(fir::FilterType::Box)
+            ));
+            assert!(matches!(
+                alg("Bilinear"),
+                fir::ResizeAlg::Convolution(fir::FilterType::Bilinear)
+            ));
+            assert!(matches!(
+                alg("Hamming"),
+                fir::ResizeAlg::Convolution(fir::FilterType::Hamming)
+            ));
+            assert!(matches!(
+                alg("CatmullRom"),
+                fir::ResizeAlg::Convolution(fir::FilterType::CatmullRom)
+            ));
+            assert!(matches!(
+                alg("Mitchell"),
+                fir::ResizeAlg::Convolution(fir::FilterType::Mitchell)
+            ));
+            assert!(matches!(
+                alg("Lanczos3"),
+                fir::ResizeAlg::Convolution(fir::FilterType::Lanczos3)
+            ));
+            assert!(matches!(
+                alg("SuperSampling"),
+                fir::ResizeAlg::SuperSampling(fir::FilterType::Lanczos3, 2)
+            ));
+        }
+
+        #[test]
+        fn none_when_name_is_unknown() {
+            assert!(ResizeFilter::from_name("Bicubic").is_none());
+            assert!(ResizeFilter::from_name("nearest").is_none());
+        }
+
+        #[test]
+        fn lanczos3_when_default() {
+            assert!(matches!(
+                ResizeFilter::default().to_resize_alg(),
+                fir::ResizeAlg::Convolution(fir::FilterType::Lanczos3)
+            ));
+        }
+    }
+
+    mod resize_options {
+        use super::*;
+
+        #[test]
+        fn default_value_when_args_are_empty() {
+            let args = vec![String::new()];
+
+            assert_eq!(
+                ResizeOptions::from_args(&args).unwrap(),
+                ResizeOptions::default()
+            );
+        }
+
+        #[test]
+        fn success_when_filter_is_specified() {
+            let args = vec![String::from("Filter=Nearest")];
+
+            let options = ResizeOptions::from_args(&args).unwrap();
+
+            assert_eq!(options.filter, ResizeFilter::Nearest);
+        }
+
+        #[test]
+        fn success_when_mode_and_background_are_specified() {
+            let args = vec![
+                String::from("Mode=Contain"),
+                String::from("Background=#FF800080"),
+            ];
+
+            let options = ResizeOptions::from_args(&args).unwrap();
+
+            assert_eq!(options.mode, ResizeMode::Contain);
+            assert_eq!(options.background, [0xFF, 0x80, 0x00, 0x80]);
+        }
+
+        #[test]
+        fn success_when_shrink_only_is_specified() {
+            let args = vec![String::from("ShrinkOnly=1")];
+
+            let options = ResizeOptions::from_args(&args).unwrap();
+
+            assert!(options.shrink_only);
+        }
+
+        #[test]
+        fn success_when_crop_rects_are_specified() {
+            let args = vec![
+                String::from("CropBefore=0,0,10,20"),
+                String::from("CropAfter=1,2,3,4"),
+            ];
+
+            let options = ResizeOptions::from_args(&args).unwrap();
+
+            assert_eq!(options.crop_before, Some("0,0,10,20".parse().unwrap()));
+            assert_eq!(options.crop_after, Some("1,2,3,4".parse().unwrap()));
+        }
+
+        #[test]
+        fn success_when_rotation_and_flip_are_specified() {
+            let args = vec![String::from("Rotate=270"), String::from("Flip=Vertical")];
+
+            let options = ResizeOptions::from_args(&args).unwrap();
+
+            assert_eq!(options.rotation, Rotation::Rotate270);
+            assert_eq!(options.flip, Flip::Vertical);
+        }
+
+        #[test]
+        fn exif_orientation_is_enabled_when_default() {
+            assert!(ResizeOptions::default().exif_orientation);
+
+            let args = vec![String::from("ExifOrientation=0")];
+            let options = ResizeOptions::from_args(&args).unwrap();
+
+            assert!(!options.exif_orientation);
+        }
+
+        #[test]
+        fn success_when_pna_is_specified() {
+            let args = vec![String::from("Pna=true"), String::from("ReadPna=false")];
+
+            let options = ResizeOptions::from_args(&args).unwrap();
+
+            assert!(options.pna);
+            assert!(!options.read_pna);
+        }
+
+        #[test]
+        fn success_when_color_key_is_specified() {
+            let args = vec![String::from("ColorKey=1")];
+
+            let options = ResizeOptions::from_args(&args).unwrap();
+
+            assert!(options.color_key);
+        }
+
+        #[test]
+        fn success_when_color_type_is_specified() {
+            let args = vec![String::from("ColorType=La16")];
+
+            let options = ResizeOptions::from_args(&args).unwrap();
+
+            assert_eq!(options.color_type, Some(image::ColorType::La16));
+        }
+
+        #[test]
+        fn failed_when_color_type_is_unknown() {
+            let args = vec![String::from("ColorType=Rgb32F")];
+
+            assert!(matches!(
+                ResizeOptions::from_args(&args),
+                Err(ResizedPngError::InvalidOption)
+            ));
+        }
+
+        #[test]
+        fn success_when_format_and_quality_are_specified() {
+            let args = vec![String::from("Format=JPEG"), String::from("Quality=90")];
+
+            let options = ResizeOptions::from_args(&args).unwrap();
+
+            assert_eq!(options.format, Some(OutputFormat::Jpeg));
+            assert_eq!(options.quality, 90);
+        }
+
+        #[test]
+        fn failed_when_quality_is_out_of_range() {
+            for value in ["0", "101", "high"] {
+                let args = vec![format!("Quality={}", value)];
+
+                assert!(matches!(
+                    ResizeOptions::from_args(&args),
+                    Err(ResizedPngError::InvalidOption)
+                ));
+            }
+        }
+
+        #[test]
+        fn success_when_png_settings_are_specified() {
+            let args = vec![
+                String::from("Compression=Best"),
+                String::from("PngFilter=Paeth"),
+                String::from("Optimize=1"),
+            ];
+
+            let options = ResizeOptions::from_args(&args).unwrap();
+
+            assert_eq!(options.compression, PngCompression::Best);
+            assert_eq!(options.png_filter, PngFilter::Paeth);
+            assert!(options.optimize);
+        }
+
+        #[test]
+        fn success_when_colors_and_dither_are_specified() {
+            let args = vec![String::from("Colors=256"), String::from("Dither=1")];
+
+            let options = ResizeOptions::from_args(&args).unwrap();
+
+            assert_eq!(options.colors, Some(256));
+            assert!(options.dither);
+        }
+
+        #[test]
+        fn failed_when_colors_is_out_of_range() {
+            for value in ["1", "257", "many"] {
+                let args = vec![format!("Colors={}", value)];
+
+                assert!(matches!(
+                    ResizeOptions::from_args(&args),
+                    Err(ResizedPngError::InvalidOption)
+                ));
+            }
+        }
+
+        #[test]
+        fn success_when_metadata_and_texts_are_specified() {
+            let args = vec![
+                String::from("Metadata=Keep"),
+                String::from("Text=Author:tukinami"),
+                String::from("Text=Comment:a:b"),
+            ];
+
+            let options = ResizeOptions::from_args(&args).unwrap();
+
+            assert_eq!(options.metadata, MetadataMode::Keep);
+            assert_eq!(
+                options.texts,
+                vec![
+                    (String::from("Author"), String::from("tukinami")),
+                    (String::from("Comment"), String::from("a:b")),
+                ]
+            );
+        }
+
+        #[test]
+        fn failed_when_text_is_malformed() {
+            for value in ["Author", ":tukinami", "作者:tukinami"] {
+                let args = vec![format!("Text={}", value)];
+
+                assert!(matches!(
+                    ResizeOptions::from_args(&args),
+                    Err(ResizedPngError::InvalidOption)
+                ));
+            }
+        }
+
+        #[test]
+        fn success_when_animation_is_specified() {
+            let args = vec![String::from("Animation=Split")];
+
+            let options = ResizeOptions::from_args(&args).unwrap();
+
+            assert_eq!(options.animation, AnimationMode::Split);
+        }
+
+        #[test]
+        fn failed_when_animation_is_unknown() {
+            let args = vec![String::from("Animation=Gif")];
+
+            assert!(matches!(
+                ResizeOptions::from_args(&args),
+                Err(ResizedPngError::InvalidOption)
+            ));
+        }
+
+        #[test]
+        fn success_when_frame_is_specified() {
+            let args = vec![String::from("Frame=2")];
+
+            let options = ResizeOptions::from_args(&args).unwrap();
+
+            assert_eq!(options.frame, Some(2));
+        }
+
+        #[test]
+        fn failed_when_frame_is_not_number() {
+            for value in ["-1", "first"] {
+                let args = vec![format!("Frame={}", value)];
+
+                assert!(matches!(
+                    ResizeOptions::from_args(&args),
+                    Err(ResizedPngError::InvalidOption)
+                ));
+            }
+        }
+
+        #[test]
+        fn success_when_trim_is_specified() {
+            let args = vec![String::from("Trim=4"), String::from("TrimThreshold=16")];
+
+            let options = ResizeOptions::from_args(&args).unwrap();
+
+            assert_eq!(options.trim, Some(4));
+            assert_eq!(options.trim_threshold, 16);
+        }
+
+        #[test]
+        fn failed_when_trim_is_invalid() {
+            for arg in ["Trim=-1", "Trim=auto", "TrimThreshold=256"] {
+                let args = vec![String::from(arg)];
+
+                assert!(matches!(
+                    ResizeOptions::from_args(&args),
+                    Err(ResizedPngError::InvalidOption)
+                ));
+            }
+        }
+
+        #[test]
+        fn success_when_linear_light_and_straight_alpha_are_specified() {
+            let args = vec![
+                String::from("LinearLight=1"),
+                String::from("StraightAlpha=1"),
+            ];
+
+            let options = ResizeOptions::from_args(&args).unwrap();
+
+            assert!(options.linear_light);
+            assert!(options.straight_alpha);
+        }
+
+        #[test]
+        fn failed_when_crop_rect_is_malformed() {
+            let args = vec![String::from("CropBefore=0,0,10")];
+
+            assert!(matches!(
+                ResizeOptions::from_args(&args),
+                Err(ResizedPngError::InvalidRect)
+            ));
+        }
+
+        #[test]
+        fn failed_when_value_is_unknown() {
+            let args = vec![String::from("Filter=Bicubic")];
+
+            assert!(matches!(
+                ResizeOptions::from_args(&args),
+                Err(ResizedPngError::InvalidOption)
+            ));
+        }
+
+        #[test]
+        fn failed_when_name_is_unknown() {
+            let args = vec![String::from("Something=Nearest")];
+
+            assert!(matches!(
+                ResizeOptions::from_args(&args),
+                Err(ResizedPngError::InvalidOption)
+            ));
+        }
+
+        #[test]
+        fn failed_when_separator_is_missing() {
+            let args = vec![String::from("Nearest")];
+
+            assert!(matches!(
+                ResizeOptions::from_args(&args),
+                Err(ResizedPngError::InvalidOption)
+            ));
+        }
+    }
+
+    mod parse_bool {
+        use super::*;
+
+        #[test]
+        fn checking_value_of_each_word() {
+            assert_eq!(parse_bool("1"), Some(true));
+            assert_eq!(parse_bool("true"), Some(true));
+            assert_eq!(parse_bool("0"), Some(false));
+            assert_eq!(parse_bool("false"), Some(false));
+            assert_eq!(parse_bool("yes"), None);
+        }
+    }
+
+    mod parse_color {
+        use super::*;
+
+        #[test]
+        fn opaque_color_when_alpha_is_omitted() {
+            assert_eq!(parse_color("#102030"), Some([0x10, 0x20, 0x30, 0xFF]));
+        }
+
+        #[test]
+        fn color_with_alpha_when_alpha_is_specified() {
+            assert_eq!(parse_color("#10203040"), Some([0x10, 0x20, 0x30, 0x40]));
+        }
+
+        #[test]
+        fn none_when_format_is_invalid() {
+            assert!(parse_color("102030").is_none());
+            assert!(parse_color("#1020").is_none());
+            assert!(parse_color("#10203G").is_none());
+            assert!(parse_color("#あいう").is_none());
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use std::{fs::File, io::BufReader, path::PathBuf};
+
+use image::DynamicImage;
+
+/// 時計回りの回転
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub(crate) enum Rotation {
+    #[default]
+    None,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl Rotation {
+    pub(crate) fn from_degrees(degrees: &str) -> Option<Rotation> {
+        match degrees {
+            "0" => Some(Rotation::None),
+            "90" => Some(Rotation::Rotate90),
+            "180" => Some(Rotation::Rotate180),
+            "270" => Some(Rotation::Rotate270),
+            _ => None,
+        }
+    }
+}
+
+/// 反転の向き
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub(crate) enum Flip {
+    #[default]
+    None,
+    /// 左右反転
+    Horizontal,
+    /// 上下反転
+    Vertical,
+    /// 左右と上下の両方の反転
+    Both,
+}
+
+impl Flip {
+    pub(crate) fn from_name(name: &str) -> Option<Flip> {
+        match name {
+            "None" => Some(Flip::None),
+            "Horizontal" => Some(Flip::Horizontal),
+            "Vertical" => Some(Flip::Vertical),
+            "Both" => Some(Flip::Both),
+            _ => None,
+        }
+    }
+}
+
+/// EXIFのOrientationタグから、正しい向きにするための回転と反転を読み取る関数。
+/// タグが読み取れない場合はNoneを返す。
+pub(crate) fn read_exif_orientation(src_path: &PathBuf) -> Option<(Rotation, Flip)> {
+    let file = File::open(src_path).ok()?;
+    let mut reader = BufReader::new(file);
+
+    let exif = exif::Reader::new().read_from_container(&mut reader).ok()?;
+    let field = exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)?;
+
+    orientation_from_exif_value(field.value.get_uint(0)?)
+}
+
+fn orientation_from_exif_value(value: u32) -> Option<(Rotation, Flip)> {
+    match value {
+        1 => Some((Rotation::None, Flip::None)),
+        2 => Some((Rotation::None, Flip::Horizontal)),
+        3 => Some((Rotation::Rotate180, Flip::None)),
+        4 => Some((Rotation::None, Flip::Vertical)),
+        5 => Some((Rotation::Rotate90, Flip::Horizontal)),
+        6 => Some((Rotation::Rotate90, Flip::None)),
+        7 => Some((Rotation::Rotate270, Flip::Horizontal)),
+        8 => Some((Rotation::Rotate270, Flip::None)),
+        _ => None,
+    }
+}
+
+/// 画像を回転させてから反転させる関数。
+pub(crate) fn apply_orientation(
+    image: DynamicImage,
+    rotation: Rotation,
+    flip: Flip,
+) -> DynamicImage {
+    let image = match rotation {
+        Rotation::None => image,
+        Rotation::Rotate90 => image.rotate90(),
+        Rotation::Rotate180 => image.rotate180(),
+        Rotation::Rotate270 => image.rotate270(),
+    };
+
+    match flip {
+        Flip::None => image,
+        Flip::Horizontal => image.fliph(),
+        Flip::Vertical => image.flipv(),
+        Flip::Both => image.rotate180(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::{GenericImageView, Rgba, RgbaImage};
+
+    /// 左上だけ色の違う2x3の画像
+    fn marked_image() -> DynamicImage {
+        let mut image = RgbaImage::from_pixel(2, 3, Rgba([0, 0, 0, 255]));
+        image.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+
+        DynamicImage::ImageRgba8(image)
+    }
+
+    fn marked_position(image: &DynamicImage) -> (u32, u32) {
+        image
+            .pixels()
+            .find(|(_, _, p)| *p == Rgba([255, 0, 0, 255]))
+            .map(|(x, y, _)| (x, y))
+            .unwrap()
+    }
+
+    mod rotation {
+        use super::*;
+
+        #[test]
+        fn checking_value_of_each_degrees() {
+            assert_eq!(Rotation::from_degrees("0"), Some(Rotation::None));
+            assert_eq!(Rotation::from_degrees("90"), Some(Rotation::Rotate90));
+            assert_eq!(Rotation::from_degrees("180"), Some(Rotation::Rotate180));
+            assert_eq!(Rotation::from_degrees("270"), Some(Rotation::Rotate270));
+            assert_eq!(Rotation::from_degrees("45"), None);
+        }
+    }
+
+    mod flip {
+        use super::*;
+
+        #[test]
+        fn checking_value_of_each_name() {
+            assert_eq!(Flip::from_name("None"), Some(Flip::None));
+            assert_eq!(Flip::from_name("Horizontal"), Some(Flip::Horizontal));
+            assert_eq!(Flip::from_name("Vertical"), Some(Flip::Vertical));
+            assert_eq!(Flip::from_name("Both"), Some(Flip::Both));
+            assert_eq!(Flip::from_name("Diagonal"), None);
+        }
+    }
+
+    mod read_exif_orientation {
+        use super::*;
+
+        #[test]
+        fn checking_value_of_each_sample() {
+            let expected = [
+                (Rotation::None, Flip::None),
+                (Rotation::None, Flip::Horizontal),
+                (Rotation::Rotate180, Flip::None),
+                (Rotation::None, Flip::Vertical),
+                (Rotation::Rotate90, Flip::Horizontal),
+                (Rotation::Rotate90, Flip::None),
+                (Rotation::Rotate270, Flip::Horizontal),
+                (Rotation::Rotate270, Flip::None),
+            ];
+
+            for (index, value) in expected.iter().enumerate() {
+                let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(format!(
+                    "test_target/image/sample_orientation_{}.jpg",
+                    index + 1
+                ));
+
+                assert_eq!(read_exif_orientation(&path).as_ref(), Some(value));
+            }
+        }
+
+        #[test]
+        fn none_when_image_has_no_exif() {
+            let path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.bmp");
+
+            assert!(read_exif_orientation(&path).is_none());
+        }
+
+        #[test]
+        fn none_when_value_is_invalid() {
+            assert!(orientation_from_exif_value(0).is_none());
+            assert!(orientation_from_exif_value(9).is_none());
+        }
+    }
+
+    mod apply_orientation {
+        use super::*;
+
+        #[test]
+        fn checking_position_of_mark_when_rotated() {
+            let image = apply_orientation(marked_image(), Rotation::Rotate90, Flip::None);
+            assert_eq!(image.dimensions(), (3, 2));
+            assert_eq!(marked_position(&image), (2, 0));
+
+            let image = apply_orientation(marked_image(), Rotation::Rotate180, Flip::None);
+            assert_eq!(image.dimensions(), (2, 3));
+            assert_eq!(marked_position(&image), (1, 2));
+
+            let image = apply_orientation(marked_image(), Rotation::Rotate270, Flip::None);
+            assert_eq!(image.dimensions(), (3, 2));
+            assert_eq!(marked_position(&image), (0, 1));
+        }
+
+        #[test]
+        fn checking_position_of_mark_when_flipped() {
+            let image = apply_orientation(marked_image(), Rotation::None, Flip::Horizontal);
+            assert_eq!(marked_position(&image), (1, 0));
+
+            let image = apply_orientation(marked_image(), Rotation::None, Flip::Vertical);
+            assert_eq!(marked_position(&image), (0, 2));
+
+            let image = apply_orientation(marked_image(), Rotation::None, Flip::Both);
+            assert_eq!(marked_position(&image), (1, 2));
+        }
+
+        #[test]
+        fn flipped_after_rotated() {
+            let image = apply_orientation(marked_image(), Rotation::Rotate90, Flip::Horizontal);
+
+            assert_eq!(image.dimensions(), (3, 2));
+            assert_eq!(marked_position(&image), (0, 0));
+        }
+    }
+}
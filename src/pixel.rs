@@ -0,0 +1,133 @@
+use std::path::PathBuf;
+
+use image::io::Reader as ImageReader;
+use image::GenericImageView;
+
+use crate::error::ResizedPngError;
+use crate::pna::merge_pna;
+
+/// 色を文字列にするときの表記
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub(crate) enum ColorNotation {
+    /// `#RRGGBBAA`
+    #[default]
+    Hex,
+    /// `R,G,B,A`(10進数)
+    Decimal,
+}
+
+impl ColorNotation {
+    pub(crate) fn from_name(name: &str) -> Option<ColorNotation> {
+        match name {
+            "Hex" => Some(ColorNotation::Hex),
+            "Decimal" => Some(ColorNotation::Decimal),
+            _ => None,
+        }
+    }
+
+    /// 色(RGBA)をこの表記の文字列にする関数。
+    pub(crate) fn format(self, color: [u8; 4]) -> String {
+        let [r, g, b, a] = color;
+
+        match self {
+            ColorNotation::Hex => format!("#{:02X}{:02X}{:02X}{:02X}", r, g, b, a),
+            ColorNotation::Decimal => format!("{},{},{},{}", r, g, b, a),
+        }
+    }
+
+    /// 透明度のない色(RGB)をこの表記の文字列にする関数。
+    pub(crate) fn format_rgb(self, color: [u8; 3]) -> String {
+        let [r, g, b] = color;
+
+        match self {
+            ColorNotation::Hex => format!("#{:02X}{:02X}{:02X}", r, g, b),
+            ColorNotation::Decimal => format!("{},{},{}", r, g, b),
+        }
+    }
+}
+
+/// 画像の指定された位置の画素の色(RGBA)を返す関数。
+/// PNAがあれば、透明度として読み込む。
+pub(crate) fn get_pixel(src_path: &PathBuf, x: i64, y: i64) -> Result<[u8; 4], ResizedPngError> {
+    let image = ImageReader::open(src_path)?
+        .with_guessed_format()?
+        .decode()?;
+    let image = merge_pna(image, src_path)?;
+
+    if !(0..image.width() as i64).contains(&x) || !(0..image.height() as i64).contains(&y) {
+        return Err(ResizedPngError::InvalidPosition);
+    }
+
+    Ok(image.get_pixel(x as u32, y as u32).0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod color_notation {
+        use super::*;
+
+        #[test]
+        fn checking_value_of_each_name() {
+            assert_eq!(ColorNotation::from_name("Hex"), Some(ColorNotation::Hex));
+            assert_eq!(
+                ColorNotation::from_name("Decimal"),
+                Some(ColorNotation::Decimal)
+            );
+            assert_eq!(ColorNotation::from_name("Rgb"), None);
+        }
+
+        #[test]
+        fn checking_value_of_each_notation() {
+            let color = [255, 128, 0, 10];
+
+            assert_eq!(ColorNotation::Hex.format(color), "#FF80000A");
+            assert_eq!(ColorNotation::Decimal.format(color), "255,128,0,10");
+            assert_eq!(ColorNotation::Hex.format_rgb([255, 128, 0]), "#FF8000");
+            assert_eq!(
+                ColorNotation::Decimal.format_rgb([255, 128, 0]),
+                "255,128,0"
+            );
+        }
+    }
+
+    mod get_pixel {
+        use super::*;
+
+        use image::{Rgba, RgbaImage};
+        use tempfile::tempdir;
+
+        #[test]
+        fn color_of_pixel_when_position_is_inside_of_image() {
+            let out_dir = tempdir().unwrap();
+            let src_path = out_dir.path().join("input.png");
+            let mut image = RgbaImage::from_pixel(4, 3, Rgba([0, 0, 0, 255]));
+            image.put_pixel(3, 2, Rgba([10, 20, 30, 40]));
+            image.save(&src_path).unwrap();
+
+            assert_eq!(get_pixel(&src_path, 3, 2).unwrap(), [10, 20, 30, 40]);
+            assert_eq!(get_pixel(&src_path, 0, 0).unwrap(), [0, 0, 0, 255]);
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn failed_when_position_is_outside_of_image() {
+            let out_dir = tempdir().unwrap();
+            let src_path = out_dir.path().join("input.png");
+            RgbaImage::from_pixel(4, 3, Rgba([0, 0, 0, 255]))
+                .save(&src_path)
+                .unwrap();
+
+            for (x, y) in [(4, 0), (0, 3), (-1, 0), (0, -1)] {
+                assert!(matches!(
+                    get_pixel(&src_path, x, y),
+                    Err(ResizedPngError::InvalidPosition)
+                ));
+            }
+
+            out_dir.close().unwrap();
+        }
+    }
+}
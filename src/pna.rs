@@ -0,0 +1,187 @@
+use std::path::{Path, PathBuf};
+
+use image::io::Reader as ImageReader;
+use image::{DynamicImage, ImageBuffer, Pixel};
+
+use crate::color::is_16bit;
+use crate::error::ResizedPngError;
+
+/// PNGファイルと対になるPNAファイルのパスを返す関数。
+pub(crate) fn pna_path(png_path: &Path) -> PathBuf {
+    png_path.with_extension("pna")
+}
+
+/// RGBAの画素列を、RGBの画素列とアルファ値の画素列に分ける関数。
+pub(crate) fn split_alpha(rgba: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let pixel_count = rgba.len() / 4;
+    let mut rgb = Vec::with_capacity(pixel_count * 3);
+    let mut alpha = Vec::with_capacity(pixel_count);
+
+    for pixel in rgba.chunks_exact(4) {
+        rgb.extend_from_slice(&pixel[..3]);
+        alpha.push(pixel[3]);
+    }
+
+    (rgb, alpha)
+}
+
+/// 入力された画像と対になるPNAファイルがあれば、そのアルファ値を画像に合成する関数。
+pub(crate) fn merge_pna(
+    image: DynamicImage,
+    src_path: &PathBuf,
+) -> Result<DynamicImage, ResizedPngError> {
+    let path = pna_path(src_path);
+    // 入力された画像自体がPNAの場合や、PNAが無い場合はそのまま返す。
+    if path == *src_path || !path.is_file() {
+        return Ok(image);
+    }
+
+    let alpha = ImageReader::open(path)?.with_guessed_format()?.decode()?;
+
+    if (alpha.width(), alpha.height()) != (image.width(), image.height()) {
+        return Err(ResizedPngError::PnaSizeError);
+    }
+
+    // 16bitの画像は、16bitのまま透明度を合成する。
+    let image = match is_16bit(image.color()) {
+        true => DynamicImage::ImageRgba16(replace_alpha(
+            image.to_rgba16(),
+            &alpha.to_luma16().into_raw(),
+        )),
+        false => DynamicImage::ImageRgba8(replace_alpha(
+            image.to_rgba8(),
+            &alpha.to_luma8().into_raw(),
+        )),
+    };
+
+    Ok(image)
+}
+
+/// RGBAの画像のアルファ値を、アルファ値の画素列で置き換える関数。
+fn replace_alpha<P: Pixel>(
+    mut image: ImageBuffer<P, Vec<P::Subpixel>>,
+    alpha: &[P::Subpixel],
+) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    for (pixel, a) in image.pixels_mut().zip(alpha.iter()) {
+        pixel.channels_mut()[3] = *a;
+    }
+
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod pna_path {
+        use super::*;
+
+        #[test]
+        fn extension_is_replaced_with_pna() {
+            let path = PathBuf::from("shell/master/surface0.png");
+
+            assert_eq!(pna_path(&path), PathBuf::from("shell/master/surface0.pna"));
+        }
+
+        #[test]
+        fn extension_is_added_when_path_has_no_extension() {
+            let path = PathBuf::from("shell/master/surface0");
+
+            assert_eq!(pna_path(&path), PathBuf::from("shell/master/surface0.pna"));
+        }
+    }
+
+    mod merge_pna {
+        use super::*;
+
+        use image::{GrayImage, Luma, Rgb, RgbImage};
+        use tempfile::tempdir;
+
+        #[test]
+        fn alpha_is_replaced_when_pna_exists() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path = out_dir.path().join("surface0.png");
+            let alpha = GrayImage::from_fn(2, 1, |x, _| Luma([x as u8 * 100]));
+            alpha
+                .save_with_format(pna_path(&src_path), image::ImageFormat::Png)
+                .unwrap();
+            let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 1, Rgb([1, 2, 3])));
+
+            let merged = merge_pna(image, &src_path).unwrap().to_rgba8();
+
+            assert_eq!(merged.into_raw(), [1, 2, 3, 0, 1, 2, 3, 100]);
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn depth_is_kept_when_image_is_16bit() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path = out_dir.path().join("surface0.png");
+            let alpha = GrayImage::from_fn(2, 1, |x, _| Luma([x as u8 * 100]));
+            alpha
+                .save_with_format(pna_path(&src_path), image::ImageFormat::Png)
+                .unwrap();
+            let image = DynamicImage::ImageRgb16(ImageBuffer::from_pixel(2, 1, Rgb([1, 2, 3])));
+
+            let merged = merge_pna(image, &src_path).unwrap();
+
+            assert_eq!(merged.color(), image::ColorType::Rgba16);
+            assert_eq!(
+                merged.to_rgba16().into_raw(),
+                [1, 2, 3, 0, 1, 2, 3, 100 * 257]
+            );
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn same_image_when_pna_does_not_exist() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path = out_dir.path().join("surface0.png");
+            let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 1, Rgb([1, 2, 3])));
+
+            let merged = merge_pna(image.clone(), &src_path).unwrap();
+
+            assert_eq!(merged, image);
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn failed_when_size_of_pna_is_different() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path = out_dir.path().join("surface0.png");
+            let alpha = GrayImage::from_pixel(3, 1, Luma([255]));
+            alpha
+                .save_with_format(pna_path(&src_path), image::ImageFormat::Png)
+                .unwrap();
+            let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 1, Rgb([1, 2, 3])));
+
+            assert!(matches!(
+                merge_pna(image, &src_path),
+                Err(ResizedPngError::PnaSizeError)
+            ));
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod split_alpha {
+        use super::*;
+
+        #[test]
+        fn rgb_and_alpha_are_separated() {
+            let rgba = [1, 2, 3, 4, 5, 6, 7, 8];
+
+            let (rgb, alpha) = split_alpha(&rgba);
+
+            assert_eq!(rgb, [1, 2, 3, 5, 6, 7]);
+            assert_eq!(alpha, [4, 8]);
+        }
+    }
+}
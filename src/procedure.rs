@@ -1,8 +1,21 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use crate::animation::frame_count;
+use crate::bounds::get_opaque_bounds;
+use crate::composite::{composite_to_png, Layer};
+use crate::dominant::{get_dominant_colors, MAX_DOMINANT_COLORS};
+use crate::error::ResizedPngError;
+use crate::info::{image_size, ImageInfo};
+use crate::options::ResizeOptions;
+use crate::orientation::{Flip, Rotation};
+use crate::pixel::{get_pixel, ColorNotation};
 use crate::request::*;
-use crate::resized_png::{get_image_type, to_resized_png};
+use crate::resized_png::{
+    crop_to_png, flip_to_png, get_image_type, rotate_to_png, to_resized_image, to_resized_png,
+    Rect, SizeCommand, MAX_OUTPUT_SIZE,
+};
 use crate::response::*;
+use crate::text::{draw_text_to_png, unescape_text, TextOptions, MAX_TEXT_SIZE};
 
 /// load時に呼ばれる関数
 pub fn load(_path: &str) {}
@@ -35,6 +48,105 @@ pub fn execute(path: &str, request: &SaoriRequest, response: &mut SaoriResponse)
                     response.set_result(v.to_string());
                 }
             }
+            "GetFrameCount" => {
+                if let Some(input_path_str) = args.get(1) {
+                    let input_path = path.join(input_path_str);
+
+                    // 失敗した場合は、エラーコードを負の数にして返す。
+                    let v = match frame_count(&input_path) {
+                        Ok(v) => v as i64,
+                        Err(e) => -(e.to_code() as i64),
+                    };
+
+                    response.set_result(format!("{}", v));
+                }
+            }
+            "GetImageSize" => {
+                if let Some(input_path_str) = args.get(1) {
+                    let input_path = path.join(input_path_str);
+
+                    // 失敗した場合は、エラーコードを負の数にして返す。
+                    match image_size(&input_path) {
+                        Ok((width, height)) => {
+                            response.set_result(format!("{}", width));
+                            response.set_value(vec![width.to_string(), height.to_string()]);
+                        }
+                        Err(e) => response.set_result(format!("-{}", e.to_code())),
+                    }
+                }
+            }
+            "GetImageInfo" => {
+                if let Some(input_path_str) = args.get(1) {
+                    let input_path = path.join(input_path_str);
+
+                    // 失敗した場合は、エラーコードを負の数にして返す。
+                    match ImageInfo::read(&input_path) {
+                        Ok(info) => {
+                            response.set_result(format!("{}", info.width));
+                            response.set_value(info.to_values());
+                        }
+                        Err(e) => response.set_result(format!("-{}", e.to_code())),
+                    }
+                }
+            }
+            "GetPixel" => {
+                if let (Some(input_path_str), Some(x_str), Some(y_str)) =
+                    (args.get(1), args.get(2), args.get(3))
+                {
+                    let input_path = path.join(input_path_str);
+
+                    // 失敗した場合は、エラーコードを負の数にして返す。
+                    match get_pixel_with_args(&input_path, x_str, y_str, args.get(4)) {
+                        Ok((color, v)) => {
+                            response.set_result(v);
+                            response.set_value(color.iter().map(|v| v.to_string()).collect());
+                        }
+                        Err(e) => response.set_result(format!("-{}", e.to_code())),
+                    }
+                }
+            }
+            "GetDominantColors" => {
+                if let (Some(input_path_str), Some(count_str)) = (args.get(1), args.get(2)) {
+                    let input_path = path.join(input_path_str);
+
+                    // 失敗した場合は、エラーコードを負の数にして返す。
+                    match get_dominant_colors_with_args(&input_path, count_str, args.get(3)) {
+                        Ok((average, values)) => {
+                            response.set_result(average);
+                            response.set_value(values);
+                        }
+                        Err(e) => response.set_result(format!("-{}", e.to_code())),
+                    }
+                }
+            }
+            "GetOpaqueBounds" => {
+                if let Some(input_path_str) = args.get(1) {
+                    let input_path = path.join(input_path_str);
+
+                    // 失敗した場合は、エラーコードを負の数にして返す。
+                    match get_opaque_bounds_with_args(
+                        &input_path,
+                        args.get(2),
+                        args.get(3..).unwrap_or_default(),
+                    ) {
+                        Ok(rect) => {
+                            // すべての画素が透明な場合は、大きさ0の範囲を返す。
+                            let values = match rect {
+                                Some(rect) => vec![
+                                    rect.left.to_string(),
+                                    rect.top.to_string(),
+                                    rect.width.to_string(),
+                                    rect.height.to_string(),
+                                ],
+                                None => vec![String::from("0"); 4],
+                            };
+                            response.set_result(values.join(","));
+                            response.set_value(values);
+                        }
+                        Err(e) => response.set_result(format!("-{}", e.to_code())),
+                    }
+                }
+            }
             "ToResizedPng" => {
                 if let (
                     Some(input_path_str),
@@ -43,17 +155,58 @@ pub fn execute(path: &str, request: &SaoriRequest, response: &mut SaoriResponse)
                     Some(height_str),
                 ) = (args.get(1), args.get(2), args.get(3), args.get(4))
                 {
-                    let Ok(width_command) = width_str.parse::<i64>() else { return };
-                    let Ok(height_command) = height_str.parse::<i64>() else { return };
+                    let input_path = path.clone().join(input_path_str);
+                    let output_path = path.join(output_path_str);
 
+                    let v = match to_resized_png_with_args(
+                        &input_path,
+                        &output_path,
+                        width_str,
+                        height_str,
+                        args.get(5..).unwrap_or_default(),
+                    ) {
+                        Ok(()) => 0,
+                        Err(e) => e.to_code(),
+                    };
+
+                    response.set_result(format!("{}", v));
+                }
+            }
+            "ToResizedImage" => {
+                if let (
+                    Some(input_path_str),
+                    Some(output_path_str),
+                    Some(width_str),
+                    Some(height_str),
+                ) = (args.get(1), args.get(2), args.get(3), args.get(4))
+                {
                     let input_path = path.clone().join(input_path_str);
                     let output_path = path.join(output_path_str);
 
-                    let v = match to_resized_png(
+                    let v = match to_resized_image_with_args(
                         &input_path,
                         &output_path,
-                        width_command,
-                        height_command,
+                        width_str,
+                        height_str,
+                        args.get(5..).unwrap_or_default(),
+                    ) {
+                        Ok(()) => 0,
+                        Err(e) => e.to_code(),
+                    };
+
+                    response.set_result(format!("{}", v));
+                }
+            }
+            "Composite" => {
+                if let (Some(base_path_str), Some(output_path_str)) = (args.get(1), args.get(2)) {
+                    let base_path = path.clone().join(base_path_str);
+                    let output_path = path.clone().join(output_path_str);
+
+                    let v = match composite_with_args(
+                        &path,
+                        &base_path,
+                        &output_path,
+                        args.get(3..).unwrap_or_default(),
                     ) {
                         Ok(()) => 0,
                         Err(e) => e.to_code(),
@@ -62,7 +215,290 @@ pub fn execute(path: &str, request: &SaoriRequest, response: &mut SaoriResponse)
                     response.set_result(format!("{}", v));
                 }
             }
+            "DrawText" => {
+                if let (
+                    Some(base_path_str),
+                    Some(output_path_str),
+                    Some(text),
+                    Some(font_path_str),
+                    Some(size_str),
+                    Some(x_str),
+                    Some(y_str),
+                ) = (
+                    args.get(1),
+                    args.get(2),
+                    args.get(3),
+                    args.get(4),
+                    args.get(5),
+                    args.get(6),
+                    args.get(7),
+                ) {
+                    // 下の画像が空の場合は、文字の大きさに合わせた画像を作る。
+                    let base_path = Some(base_path_str)
+                        .filter(|v| !v.is_empty())
+                        .map(|v| path.join(v));
+                    let output_path = path.join(output_path_str);
+                    let font_path = path.join(font_path_str);
+
+                    let v = match draw_text_with_args(
+                        base_path.as_ref(),
+                        &output_path,
+                        text,
+                        &font_path,
+                        (size_str, x_str, y_str),
+                        args.get(8..).unwrap_or_default(),
+                    ) {
+                        Ok(()) => 0,
+                        Err(e) => e.to_code(),
+                    };
+
+                    response.set_result(format!("{}", v));
+                }
+            }
+            "Crop" => {
+                if let (
+                    Some(input_path_str),
+                    Some(output_path_str),
+                    Some(left_str),
+                    Some(top_str),
+                    Some(width_str),
+                    Some(height_str),
+                ) = (
+                    args.get(1),
+                    args.get(2),
+                    args.get(3),
+                    args.get(4),
+                    args.get(5),
+                    args.get(6),
+                ) {
+                    let input_path = path.clone().join(input_path_str);
+                    let output_path = path.join(output_path_str);
+
+                    let v = match Rect::from_strs(left_str, top_str, width_str, height_str)
+                        .and_then(|rect| crop_to_png(&input_path, &output_path, rect))
+                    {
+                        Ok(()) => 0,
+                        Err(e) => e.to_code(),
+                    };
+
+                    response.set_result(format!("{}", v));
+                }
+            }
+            "Rotate" => {
+                if let (Some(input_path_str), Some(output_path_str), Some(degrees_str)) =
+                    (args.get(1), args.get(2), args.get(3))
+                {
+                    let input_path = path.clone().join(input_path_str);
+                    let output_path = path.join(output_path_str);
+
+                    let v = match Rotation::from_degrees(degrees_str)
+                        .ok_or(ResizedPngError::InvalidOption)
+                        .and_then(|rotation| rotate_to_png(&input_path, &output_path, rotation))
+                    {
+                        Ok(()) => 0,
+                        Err(e) => e.to_code(),
+                    };
+
+                    response.set_result(format!("{}", v));
+                }
+            }
+            "Flip" => {
+                if let (Some(input_path_str), Some(output_path_str), Some(direction_str)) =
+                    (args.get(1), args.get(2), args.get(3))
+                {
+                    let input_path = path.clone().join(input_path_str);
+                    let output_path = path.join(output_path_str);
+
+                    let v = match Flip::from_name(direction_str)
+                        .ok_or(ResizedPngError::InvalidOption)
+                        .and_then(|flip| flip_to_png(&input_path, &output_path, flip))
+                    {
+                        Ok(()) => 0,
+                        Err(e) => e.to_code(),
+                    };
+
+                    response.set_result(format!("{}", v));
+                }
+            }
             _ => {}
         }
     }
 }
+
+/// ToResizedPngの引数を読み取って実行する関数。
+fn to_resized_png_with_args(
+    input_path: &PathBuf,
+    output_path: &Path,
+    width_str: &str,
+    height_str: &str,
+    option_args: &[String],
+) -> Result<(), ResizedPngError> {
+    let width_command = width_str.parse::<SizeCommand>()?;
+    let height_command = height_str.parse::<SizeCommand>()?;
+    let options = ResizeOptions::from_args(option_args)?;
+
+    to_resized_png(
+        input_path,
+        output_path,
+        width_command,
+        height_command,
+        &options,
+    )
+}
+
+/// ToResizedImageの引数を読み取って実行する関数。
+fn to_resized_image_with_args(
+    input_path: &PathBuf,
+    output_path: &Path,
+    width_str: &str,
+    height_str: &str,
+    option_args: &[String],
+) -> Result<(), ResizedPngError> {
+    let width_command = width_str.parse::<SizeCommand>()?;
+    let height_command = height_str.parse::<SizeCommand>()?;
+    let options = ResizeOptions::from_args(option_args)?;
+
+    to_resized_image(
+        input_path,
+        output_path,
+        width_command,
+        height_command,
+        &options,
+    )
+}
+
+/// GetPixelの引数を読み取って実行する関数。
+/// 色と、指定された表記の文字列を返す。
+fn get_pixel_with_args(
+    input_path: &PathBuf,
+    x_str: &str,
+    y_str: &str,
+    notation_str: Option<&String>,
+) -> Result<([u8; 4], String), ResizedPngError> {
+    let x = x_str
+        .parse::<i64>()
+        .map_err(|_| ResizedPngError::InvalidOption)?;
+    let y = y_str
+        .parse::<i64>()
+        .map_err(|_| ResizedPngError::InvalidOption)?;
+    let notation = parse_notation(notation_str)?;
+
+    let color = get_pixel(input_path, x, y)?;
+
+    Ok((color, notation.format(color)))
+}
+
+/// GetDominantColorsの引数を読み取って実行する関数。
+/// 平均の色と、主な色と割合の文字列を返す。
+fn get_dominant_colors_with_args(
+    input_path: &PathBuf,
+    count_str: &str,
+    notation_str: Option<&String>,
+) -> Result<(String, Vec<String>), ResizedPngError> {
+    let count = count_str
+        .parse::<usize>()
+        .ok()
+        .filter(|v| (1..=MAX_DOMINANT_COLORS).contains(v))
+        .ok_or(ResizedPngError::InvalidOption)?;
+    let notation = parse_notation(notation_str)?;
+
+    let (average, colors) = get_dominant_colors(input_path, count)?;
+
+    let average = average.map(|v| notation.format_rgb(v)).unwrap_or_default();
+    let values = colors
+        .iter()
+        .map(|v| format!("{},{:.3}", notation.format_rgb(v.color), v.proportion))
+        .collect();
+
+    Ok((average, values))
+}
+
+/// 色の表記の引数を読み取る関数。
+/// 省略された場合は既定の表記にする。
+fn parse_notation(notation_str: Option<&String>) -> Result<ColorNotation, ResizedPngError> {
+    match notation_str.filter(|v| !v.is_empty()) {
+        Some(v) => ColorNotation::from_name(v).ok_or(ResizedPngError::InvalidOption),
+        None => Ok(ColorNotation::default()),
+    }
+}
+
+/// GetOpaqueBoundsの引数を読み取って実行する関数。
+/// 追加の設定はToResizedPngと同じ形式で指定する。
+fn get_opaque_bounds_with_args(
+    input_path: &PathBuf,
+    threshold_str: Option<&String>,
+    option_args: &[String],
+) -> Result<Option<Rect>, ResizedPngError> {
+    let threshold = match threshold_str.filter(|v| !v.is_empty()) {
+        Some(v) => v
+            .parse::<u8>()
+            .map_err(|_| ResizedPngError::InvalidOption)?,
+        None => 0,
+    };
+
+    let options = ResizeOptions::from_args(option_args)?;
+
+    get_opaque_bounds(input_path, threshold, &options)
+}
+
+/// Compositeの引数を読み取って実行する関数。
+/// 重ねる画像は、パス、左端からの位置、上端からの位置、混ぜ方の4つずつで指定する。
+fn composite_with_args(
+    path: &Path,
+    base_path: &PathBuf,
+    output_path: &Path,
+    layer_args: &[String],
+) -> Result<(), ResizedPngError> {
+    // 末尾の空の引数は無視する。
+    let len = layer_args
+        .iter()
+        .rposition(|v| !v.is_empty())
+        .map_or(0, |v| v + 1);
+    if len % 4 != 0 {
+        return Err(ResizedPngError::InvalidOption);
+    }
+
+    let layers = layer_args[..len]
+        .chunks_exact(4)
+        .map(|v| Layer::from_strs(path.join(&v[0]), &v[1], &v[2], &v[3]))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    composite_to_png(base_path, output_path, &layers)
+}
+
+/// DrawTextの引数を読み取って実行する関数。
+/// 数値の引数は、文字の大きさ、左端からの位置、上端からの位置の順。
+fn draw_text_with_args(
+    base_path: Option<&PathBuf>,
+    output_path: &Path,
+    text: &str,
+    font_path: &Path,
+    (size_str, x_str, y_str): (&str, &str, &str),
+    option_args: &[String],
+) -> Result<(), ResizedPngError> {
+    let size = size_str
+        .parse::<f32>()
+        .ok()
+        .filter(|v| v.is_finite() && *v > 0.0 && *v <= MAX_TEXT_SIZE)
+        .ok_or(ResizedPngError::InvalidOption)?;
+    // 位置は、出力できる画像の大きさの範囲に限る。
+    let parse_position = |v: &str| {
+        v.parse::<i64>()
+            .ok()
+            .filter(|v| v.abs() <= MAX_OUTPUT_SIZE as i64)
+            .ok_or(ResizedPngError::InvalidOption)
+    };
+    let x = parse_position(x_str)?;
+    let y = parse_position(y_str)?;
+    let options = TextOptions::from_args(option_args)?;
+
+    draw_text_to_png(
+        base_path,
+        output_path,
+        &unescape_text(text),
+        font_path,
+        size,
+        (x, y),
+        &options,
+    )
+}
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+
+use color_quant::NeuQuant;
+use image::{imageops, DynamicImage, Rgba, RgbaImage};
+
+/// NeuQuantの標本化の間隔(小さいほど高品質で遅い)
+const SAMPLE_FACTOR: i32 = 10;
+
+/// 減色した画像
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct IndexedImage {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    /// パレットの色(RGBA)。透明度を持つ色を先に並べる
+    pub(crate) palette: Vec<[u8; 4]>,
+    /// 各画素のパレットの番号
+    pub(crate) indices: Vec<u8>,
+}
+
+/// 画像を指定された色数(2から256)以下のパレットに減色する関数。
+/// 元から色数が収まっている場合は、色を変えずにパレットにする。
+pub(crate) fn quantize(image: &DynamicImage, max_colors: u16, dither: bool) -> IndexedImage {
+    let mut image = image.to_rgba8();
+
+    // 完全に透明な画素は色を揃えて、パレットを無駄にしないようにする。
+    for pixel in image.pixels_mut() {
+        if pixel.0[3] == 0 {
+            pixel.0 = [0, 0, 0, 0];
+        }
+    }
+
+    if let Some(v) = exact_palette(&image, max_colors as usize) {
+        return v;
+    }
+
+    let quant = NeuQuant::new(SAMPLE_FACTOR, max_colors as usize, image.as_raw());
+
+    // 誤差拡散は右と下の画素に広げるため、幅か高さが1の画像には行わない。
+    if dither && image.width() > 1 && image.height() > 1 {
+        imageops::dither(&mut image, &quant);
+    }
+
+    let indices = imageops::index_colors(&image, &quant).into_raw();
+    let palette = quant
+        .color_map_rgba()
+        .chunks_exact(4)
+        .map(|v| [v[0], v[1], v[2], v[3]])
+        .collect();
+
+    compact_palette(image.width(), image.height(), palette, indices)
+}
+
+/// 色数が収まっている場合に、画像の色をそのままパレットにする関数。
+fn exact_palette(image: &RgbaImage, max_colors: usize) -> Option<IndexedImage> {
+    let mut colors: HashMap<[u8; 4], u8> = HashMap::new();
+    let mut palette = Vec::new();
+    let mut indices = Vec::with_capacity(image.width() as usize * image.height() as usize);
+
+    for Rgba(color) in image.pixels() {
+        let index = match colors.get(color) {
+            Some(v) => *v,
+            None => {
+                if palette.len() >= max_colors {
+                    return None;
+                }
+                let index = palette.len() as u8;
+                colors.insert(*color, index);
+                palette.push(*color);
+                index
+            }
+        };
+        indices.push(index);
+    }
+
+    Some(compact_palette(
+        image.width(),
+        image.height(),
+        palette,
+        indices,
+    ))
+}
+
+/// 使われていない色をパレットから除き、透明度を持つ色を先に並べ直す関数。
+fn compact_palette(
+    width: u32,
+    height: u32,
+    palette: Vec<[u8; 4]>,
+    indices: Vec<u8>,
+) -> IndexedImage {
+    let mut used = vec![false; palette.len()];
+    for index in indices.iter() {
+        used[*index as usize] = true;
+    }
+
+    let mut order: Vec<usize> = (0..palette.len()).filter(|v| used[*v]).collect();
+    order.sort_by_key(|v| palette[*v][3] == 255);
+
+    let mut remap = vec![0; palette.len()];
+    for (new_index, old_index) in order.iter().enumerate() {
+        remap[*old_index] = new_index as u8;
+    }
+
+    IndexedImage {
+        width,
+        height,
+        palette: order.iter().map(|v| palette[*v]).collect(),
+        indices: indices.iter().map(|v| remap[*v as usize]).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashSet;
+
+    /// 色の異なる画素が多いグラデーションの画像
+    fn gradation_image() -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(64, 64, |x, y| {
+            Rgba([(x * 4) as u8, (y * 4) as u8, ((x + y) * 2) as u8, 255])
+        }))
+    }
+
+    fn unique_colors(image: &IndexedImage) -> HashSet<[u8; 4]> {
+        image
+            .indices
+            .iter()
+            .map(|v| image.palette[*v as usize])
+            .collect()
+    }
+
+    mod quantize {
+        use super::*;
+
+        #[test]
+        fn at_most_max_colors_when_image_has_many_colors() {
+            for max_colors in [2, 16, 256] {
+                for dither in [false, true] {
+                    let output = quantize(&gradation_image(), max_colors, dither);
+
+                    assert!(output.palette.len() <= max_colors as usize);
+                    assert!(unique_colors(&output).len() <= max_colors as usize);
+                    assert_eq!(output.indices.len(), 64 * 64);
+                }
+            }
+        }
+
+        #[test]
+        fn same_colors_when_image_has_few_colors() {
+            let mut image = RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 255]));
+            image.put_pixel(1, 1, Rgba([0, 255, 0, 128]));
+            image.put_pixel(2, 2, Rgba([0, 0, 255, 255]));
+
+            let output = quantize(&DynamicImage::ImageRgba8(image.clone()), 4, true);
+
+            assert_eq!(output.palette.len(), 3);
+            for (pixel, index) in image.pixels().zip(output.indices.iter()) {
+                assert_eq!(output.palette[*index as usize], pixel.0);
+            }
+        }
+
+        #[test]
+        fn one_transparent_color_when_transparent_pixels_have_different_colors() {
+            let mut image = RgbaImage::from_pixel(2, 1, Rgba([255, 0, 0, 0]));
+            image.put_pixel(1, 0, Rgba([0, 255, 0, 0]));
+
+            let output = quantize(&DynamicImage::ImageRgba8(image), 2, false);
+
+            assert_eq!(output.palette, vec![[0, 0, 0, 0]]);
+        }
+
+        #[test]
+        fn success_when_image_is_single_row() {
+            let image = DynamicImage::ImageRgba8(RgbaImage::from_fn(64, 1, |x, _| {
+                Rgba([(x * 4) as u8, 0, 0, 255])
+            }));
+
+            let output = quantize(&image, 4, true);
+
+            assert!(unique_colors(&output).len() <= 4);
+        }
+    }
+
+    mod compact_palette {
+        use super::*;
+
+        #[test]
+        fn transparent_colors_first_and_unused_colors_removed() {
+            let palette = vec![[1, 1, 1, 255], [2, 2, 2, 255], [3, 3, 3, 0]];
+            let indices = vec![0, 2, 0];
+
+            let output = compact_palette(3, 1, palette, indices);
+
+            assert_eq!(output.palette, vec![[3, 3, 3, 0], [1, 1, 1, 255]]);
+            assert_eq!(output.indices, vec![1, 0, 1]);
+        }
+    }
+}
@@ -1,9 +1,28 @@
-use std::{num::NonZeroU32, path::PathBuf};
+use std::{
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use fast_image_resize as fir;
 use image::io::Reader as ImageReader;
+use image::{Delay, DynamicImage, ImageBuffer, Pixel, Primitive};
 
+use crate::animation::{delay_to_millis, numbered_path, read_frames, AnimationMode};
+use crate::bounds::trim_rect;
+use crate::color::{
+    color_type_with_alpha, convert_color, has_alpha, is_16bit, pixel_bytes, to_dynamic_image,
+    to_fir_image, to_u16_pixel_type,
+};
 use crate::error::ResizedPngError;
+use crate::format::{save_apng, save_image, OutputFormat};
+use crate::metadata::{MetadataMode, PngMetadata};
+use crate::options::{ResizeMode, ResizeOptions};
+use crate::orientation::{apply_orientation, read_exif_orientation, Flip, Rotation};
+use crate::pna::{merge_pna, pna_path, split_alpha};
+
+/// 出力する画像の横幅と縦幅の最大値
+pub(crate) const MAX_OUTPUT_SIZE: u32 = 16384;
 
 pub(crate) fn get_image_type(src_path: &PathBuf) -> &'static str {
     let Ok(reader) = ImageReader::open(src_path).and_then(|v| v.with_guessed_format()) else {
@@ -23,6 +42,7 @@ pub(crate) fn get_image_type(src_path: &PathBuf) -> &'static str {
             image::ImageFormat::OpenExr => "OPENEXR",
             image::ImageFormat::Png => "PNG",
             image::ImageFormat::Pnm => "PNM",
+            image::ImageFormat::Qoi => "QOI",
             image::ImageFormat::Tga => "TGA",
             image::ImageFormat::Tiff => "TIFF",
             image::ImageFormat::WebP => "WEBP",
@@ -32,62 +52,774 @@ pub(crate) fn get_image_type(src_path: &PathBuf) -> &'static str {
     }
 }
 
+/// 画像を拡大縮小してpngとして出力する関数。
+/// 設定の出力する形式は無視する。
 pub(crate) fn to_resized_png(
     src_path: &PathBuf,
-    dist_path: &PathBuf,
-    width_command: i64,
-    height_command: i64,
+    dist_path: &Path,
+    width_command: SizeCommand,
+    height_command: SizeCommand,
+    options: &ResizeOptions,
 ) -> Result<(), ResizedPngError> {
-    let reader = ImageReader::open(src_path).and_then(|v| v.with_guessed_format())?;
-    let input_img = reader.decode()?;
+    let options = ResizeOptions {
+        format: Some(OutputFormat::Png),
+        ..options.clone()
+    };
+
+    to_resized_image(src_path, dist_path, width_command, height_command, &options)
+}
+
+/// 画像を拡大縮小して、設定または出力するファイルの拡張子の形式で出力する関数。
+pub(crate) fn to_resized_image(
+    src_path: &PathBuf,
+    dist_path: &Path,
+    width_command: SizeCommand,
+    height_command: SizeCommand,
+    options: &ResizeOptions,
+) -> Result<(), ResizedPngError> {
+    let format = match options.format {
+        Some(v) => v,
+        None => OutputFormat::from_extension(dist_path).ok_or(ResizedPngError::Unsupported)?,
+    };
+
+    // APNGはフレームごとにパレットを変えられないため、減色はできない。
+    // 1つのフレームを取り出す場合は、アニメーションとして出力できない。
+    match options.animation {
+        AnimationMode::Apng | AnimationMode::Split if options.frame.is_some() => {
+            return Err(ResizedPngError::InvalidOption)
+        }
+        AnimationMode::Apng if format != OutputFormat::Png => {
+            return Err(ResizedPngError::Unsupported)
+        }
+        AnimationMode::Apng if options.pna || options.colors.is_some() => {
+            return Err(ResizedPngError::InvalidOption)
+        }
+        AnimationMode::Split if numbered_path(dist_path, 0).is_none() => {
+            return Err(ResizedPngError::InvalidOption)
+        }
+        _ => {}
+    }
+
+    let frames = read_prepared_frames(src_path, options)?;
+
+    // すべてのフレームが同じ大きさになるよう、透明な余白はまとめて調べる。
+    let trim = match options.trim {
+        Some(margin) => {
+            let images: Vec<&DynamicImage> = frames.iter().map(|(v, _)| v).collect();
+            trim_rect(&images, margin, options.trim_threshold)
+        }
+        None => None,
+    };
+
+    let mut output_frames = Vec::with_capacity(frames.len());
+    for (input_img, delay) in frames {
+        match resize_frame(input_img, trim, width_command, height_command, options)? {
+            Some(v) => output_frames.push((v, delay)),
+            // サイズが計算できないときは、何もせず終了。
+            None => return Ok(()),
+        }
+    }
+
+    let mut metadata = match options.metadata {
+        MetadataMode::Keep => PngMetadata::read(src_path),
+        MetadataMode::Strip => PngMetadata::default(),
+    };
+    for (keyword, text) in options.texts.iter() {
+        metadata.add_text(keyword, text);
+    }
+
+    match options.animation {
+        AnimationMode::None => {
+            let (output_img, _) = output_frames.swap_remove(0);
+            save_output_image(output_img, dist_path, format, options, &metadata)
+        }
+        AnimationMode::Apng => {
+            let frames = output_frames
+                .into_iter()
+                .map(|(image, delay)| (image, delay_to_millis(delay)))
+                .collect();
+            save_apng(frames, dist_path, options, &metadata)
+        }
+        AnimationMode::Split => {
+            for (i, (output_img, _)) in output_frames.into_iter().enumerate() {
+                let frame_path =
+                    numbered_path(dist_path, i).ok_or(ResizedPngError::InvalidOption)?;
+                save_output_image(output_img, &frame_path, format, options, &metadata)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// 画像のフレームを読み込み、PNAと透過色と向きを反映する関数。
+/// アニメーションしない画像や、アニメーションとして扱わない場合は1つのフレームにする。
+pub(crate) fn read_prepared_frames(
+    src_path: &PathBuf,
+    options: &ResizeOptions,
+) -> Result<Vec<(DynamicImage, Delay)>, ResizedPngError> {
+    // アニメーションしない画像は、1つのフレームとして扱う。
+    let frames = match (options.animation, options.frame) {
+        (AnimationMode::None, None) => None,
+        _ => read_frames(src_path)?,
+    };
+    let mut frames = match frames {
+        Some(v) => v,
+        None => {
+            vec![(decode_image(src_path)?, Delay::from_numer_denom_ms(0, 1))]
+        }
+    };
+    if let Some(index) = options.frame {
+        if index >= frames.len() {
+            return Err(ResizedPngError::InvalidOption);
+        }
+        frames = vec![frames.swap_remove(index)];
+    }
+
+    frames
+        .into_iter()
+        .map(|(input_img, delay)| Ok((prepare_frame(input_img, src_path, options)?, delay)))
+        .collect()
+}
+
+/// 1つのフレームに、PNAと透過色と向きを反映する関数。
+fn prepare_frame(
+    input_img: DynamicImage,
+    src_path: &PathBuf,
+    options: &ResizeOptions,
+) -> Result<DynamicImage, ResizedPngError> {
+    let input_img = match options.read_pna {
+        true => merge_pna(input_img, src_path)?,
+        false => input_img,
+    };
+
+    let input_img = match options.color_key {
+        true => apply_color_key(input_img),
+        false => input_img,
+    };
 
-    let pixel_type = fir::PixelType::U8x4;
+    // EXIFの向きを直してから、指定された回転・反転を行う。
+    let exif_orientation = match options.exif_orientation {
+        true => read_exif_orientation(src_path),
+        false => None,
+    };
+    let input_img = match exif_orientation {
+        Some((rotation, flip)) => apply_orientation(input_img, rotation, flip),
+        None => input_img,
+    };
+
+    Ok(apply_orientation(input_img, options.rotation, options.flip))
+}
+
+/// 1つのフレームを拡大縮小する関数。
+/// 透明な余白を切り取る範囲があれば、先に切り取る。
+/// サイズが計算できないときは`None`を返す。
+fn resize_frame(
+    input_img: DynamicImage,
+    trim: Option<Rect>,
+    width_command: SizeCommand,
+    height_command: SizeCommand,
+    options: &ResizeOptions,
+) -> Result<Option<DynamicImage>, ResizedPngError> {
+    // 元の色の種類のまま扱う。
+    let input_image = to_fir_image(input_img)?;
+
+    // 透明な余白を切り取ってから、指定された範囲を切り取る。
+    let input_image = match trim {
+        Some(rect) => crop_image(&input_image, rect),
+        None => input_image,
+    };
+    let (input_width, input_height) = (input_image.width(), input_image.height());
+
+    // 拡大縮小の前に切り取る。
+    let mut input_image = match options.crop_before {
+        Some(rect) => {
+            rect.check_inside(input_width, input_height)?;
+            crop_image(&input_image, rect)
+        }
+        None => input_image,
+    };
+    let (input_width, input_height) = (input_image.width(), input_image.height());
 
-    let (input_width, input_height) = NonZeroU32::new(input_img.width())
-        .zip(NonZeroU32::new(input_img.height()))
-        .ok_or(ResizedPngError::InputSizeError)?;
+    // サイズが計算できないときは、何もしない。
+    let (output_width, output_height) = match output_size(
+        width_command.to_pixels(input_width)?,
+        height_command.to_pixels(input_height)?,
+        input_width,
+        input_height,
+    ) {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+    // 元の大きさのままの場合は、大きさの上限を超えていてもよい。
+    if (output_width != input_width && output_width.get() > MAX_OUTPUT_SIZE)
+        || (output_height != input_height && output_height.get() > MAX_OUTPUT_SIZE)
+    {
+        return Err(ResizedPngError::InvalidSize);
+    }
 
-    let mut input_image = fir::Image::from_vec_u8(
+    let layout = output_layout(
+        output_width,
+        output_height,
         input_width,
         input_height,
-        input_img.to_rgba8().into_raw(),
-        pixel_type,
-    )?;
+        options.mode,
+        options.shrink_only,
+    );
 
-    let alpha_mul_div = fir::MulDiv::default();
-    alpha_mul_div
-        .divide_alpha_inplace(&mut input_image.view_mut())
-        .expect("limited target pixel type.");
-
-    // サイズが計算できないときは、何もせず終了。
-    let (output_width, output_height) =
-        match output_size(width_command, height_command, input_width, input_height) {
-            Some(v) => v,
-            None => return Ok(()),
+    // 大きさが変わらない場合は、拡大縮小せずにそのまま使う。
+    let output_image =
+        if layout.src.width == layout.resized_width && layout.src.height == layout.resized_height {
+            crop_image(&input_image, layout.src)
+        } else {
+            resize_image(&mut input_image, &layout, options)
         };
 
-    let mut output_image = fir::Image::new(output_width, output_height, input_image.pixel_type());
+    let output_image = place_on_canvas(output_image, &layout, options.background);
+
+    // 拡大縮小の後に切り取る。
+    let output_image = match options.crop_after {
+        Some(rect) => {
+            rect.check_inside(output_image.width(), output_image.height())?;
+            crop_image(&output_image, rect)
+        }
+        None => output_image,
+    };
+
+    let output_img = to_dynamic_image(output_image);
+    let output_img = match options.color_type {
+        Some(color_type) => convert_color(output_img, color_type),
+        None => output_img,
+    };
+
+    Ok(Some(output_img))
+}
+
+/// 画像を読み込む関数。
+pub(crate) fn decode_image(src_path: &PathBuf) -> Result<DynamicImage, ResizedPngError> {
+    let reader = ImageReader::open(src_path).and_then(|v| v.with_guessed_format())?;
+
+    Ok(reader.decode()?)
+}
+
+/// 画像を既定の設定でpngとして出力する関数。
+pub(crate) fn save_png(image: DynamicImage, dist_path: &Path) -> Result<(), ResizedPngError> {
+    save_output_image(
+        image,
+        dist_path,
+        OutputFormat::Png,
+        &ResizeOptions::default(),
+        &PngMetadata::default(),
+    )
+}
+
+/// 拡大縮小した画像を、設定に従って出力する関数。
+fn save_output_image(
+    output_img: DynamicImage,
+    dist_path: &Path,
+    format: OutputFormat,
+    options: &ResizeOptions,
+    metadata: &PngMetadata,
+) -> Result<(), ResizedPngError> {
+    if options.pna {
+        // アルファ値はPNAに、色はRGBの画像に出力する。
+        let output_img = output_img.to_rgba8();
+        let (width, height) = output_img.dimensions();
+        let (rgb, alpha) = split_alpha(output_img.as_raw());
+
+        let rgb_img = image::RgbImage::from_raw(width, height, rgb).expect("buffer size is same");
+        let alpha_img =
+            image::GrayImage::from_raw(width, height, alpha).expect("buffer size is same");
+        save_image(
+            DynamicImage::ImageRgb8(rgb_img),
+            dist_path,
+            format,
+            options,
+            metadata,
+        )?;
+        // PNAはグレースケールのまま出力する。
+        let pna_options = ResizeOptions {
+            colors: None,
+            ..options.clone()
+        };
+        save_image(
+            DynamicImage::ImageLuma8(alpha_img),
+            &pna_path(dist_path),
+            OutputFormat::Png,
+            &pna_options,
+            metadata,
+        )?;
+    } else {
+        save_image(output_img, dist_path, format, options, metadata)?;
+    }
+
+    Ok(())
+}
+
+/// 左上の画素と同じ色の画素を透明にする関数。
+fn apply_color_key(image: DynamicImage) -> DynamicImage {
+    // 16bitの画像は、16bitのまま色を比べる。
+    match is_16bit(image.color()) {
+        true => DynamicImage::ImageRgba16(clear_key_color(image.to_rgba16())),
+        false => DynamicImage::ImageRgba8(clear_key_color(image.to_rgba8())),
+    }
+}
+
+/// RGBAの画像で、左上の画素と同じ色の画素を透明にする関数。
+fn clear_key_color<P: Pixel>(
+    mut image: ImageBuffer<P, Vec<P::Subpixel>>,
+) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    let key = *image.get_pixel(0, 0);
+    for pixel in image.pixels_mut() {
+        if pixel.channels()[..3] == key.channels()[..3] {
+            pixel
+                .channels_mut()
+                .fill(<P::Subpixel as Primitive>::DEFAULT_MIN_VALUE);
+        }
+    }
+
+    image
+}
+
+/// 画像を拡大縮小せずに切り取って出力する関数。
+pub(crate) fn crop_to_png(
+    src_path: &PathBuf,
+    dist_path: &Path,
+    rect: Rect,
+) -> Result<(), ResizedPngError> {
+    let options = ResizeOptions {
+        crop_before: Some(rect),
+        ..Default::default()
+    };
+
+    to_resized_png(
+        src_path,
+        dist_path,
+        SizeCommand::Pixel(0),
+        SizeCommand::Pixel(0),
+        &options,
+    )
+}
+
+/// 画像を拡大縮小せずに回転させて出力する関数。
+pub(crate) fn rotate_to_png(
+    src_path: &PathBuf,
+    dist_path: &Path,
+    rotation: Rotation,
+) -> Result<(), ResizedPngError> {
+    let options = ResizeOptions {
+        rotation,
+        ..Default::default()
+    };
+
+    to_resized_png(
+        src_path,
+        dist_path,
+        SizeCommand::Pixel(0),
+        SizeCommand::Pixel(0),
+        &options,
+    )
+}
+
+/// 画像を拡大縮小せずに反転させて出力する関数。
+pub(crate) fn flip_to_png(
+    src_path: &PathBuf,
+    dist_path: &Path,
+    flip: Flip,
+) -> Result<(), ResizedPngError> {
+    let options = ResizeOptions {
+        flip,
+        ..Default::default()
+    };
+
+    to_resized_png(
+        src_path,
+        dist_path,
+        SizeCommand::Pixel(0),
+        SizeCommand::Pixel(0),
+        &options,
+    )
+}
+
+/// 配置に従って画像を拡大縮小する関数。
+fn resize_image(
+    input_image: &mut fir::Image,
+    layout: &OutputLayout,
+    options: &ResizeOptions,
+) -> fir::Image<'static> {
+    let srgb_mapper = fir::create_srgb_mapper();
+
+    let pixel_type = input_image.pixel_type();
+
+    // リニアにする場合は、精度を保つために16bitで拡大縮小する。
+    let mut linear_image;
+    let work_image = match options.linear_light {
+        true => {
+            linear_image = fir::Image::new(
+                input_image.width(),
+                input_image.height(),
+                to_u16_pixel_type(pixel_type),
+            );
+            srgb_mapper
+                .forward_map(&input_image.view(), &mut linear_image.view_mut())
+                .expect("limited target pixel type.");
+            &mut linear_image
+        }
+        false => input_image,
+    };
+
+    // 半透明の縁に透明部分の色が混ざらないよう、アルファ値を乗算してから拡大縮小する。
+    let alpha_mul_div = fir::MulDiv::default();
+    let premultiply = !options.straight_alpha && has_alpha(pixel_type);
+    if premultiply {
+        alpha_mul_div
+            .multiply_alpha_inplace(&mut work_image.view_mut())
+            .expect("limited target pixel type.");
+    }
+
+    let mut work_view = work_image.view();
+    work_view
+        .set_crop_box(layout.src.to_crop_box())
+        .expect("crop box is inside of image");
+
+    let mut output_image = fir::Image::new(
+        layout.resized_width,
+        layout.resized_height,
+        work_image.pixel_type(),
+    );
     let mut output_view = output_image.view_mut();
 
-    let mut resizer = fir::Resizer::new(fir::ResizeAlg::Convolution(fir::FilterType::Lanczos3));
+    let mut resizer = fir::Resizer::new(options.filter.to_resize_alg());
     resizer
-        .resize(&input_image.view(), &mut output_view)
+        .resize(&work_view, &mut output_view)
         .expect("pixel type is same");
 
-    alpha_mul_div
-        .divide_alpha_inplace(&mut output_view)
-        .expect("limited target pixel type.");
+    if premultiply {
+        alpha_mul_div
+            .divide_alpha_inplace(&mut output_view)
+            .expect("limited target pixel type.");
+    }
 
-    image::save_buffer_with_format(
-        dist_path,
-        output_image.buffer(),
-        output_image.width().get(),
-        output_image.height().get(),
-        image::ColorType::Rgba8,
-        image::ImageFormat::Png,
-    )?;
+    match options.linear_light {
+        true => {
+            let mut srgb_image =
+                fir::Image::new(output_image.width(), output_image.height(), pixel_type);
+            srgb_mapper
+                .backward_map(&output_image.view(), &mut srgb_image.view_mut())
+                .expect("limited target pixel type.");
+            srgb_image
+        }
+        false => output_image,
+    }
+}
 
-    Ok(())
+/// 画像から矩形範囲を切り出す関数。
+fn crop_image(image: &fir::Image, rect: Rect) -> fir::Image<'static> {
+    let pixel_size =
+        image.buffer().len() / (image.width().get() as usize * image.height().get() as usize);
+    let row_size = image.width().get() as usize * pixel_size;
+    let start = rect.left as usize * pixel_size;
+    let end = start + rect.width.get() as usize * pixel_size;
+
+    let buffer: Vec<u8> = image
+        .buffer()
+        .chunks_exact(row_size)
+        .skip(rect.top as usize)
+        .take(rect.height.get() as usize)
+        .flat_map(|row| &row[start..end])
+        .copied()
+        .collect();
+
+    fir::Image::from_vec_u8(rect.width, rect.height, buffer, image.pixel_type())
+        .expect("buffer size is same as rect")
+}
+
+/// 出力する大きさの指定
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum SizeCommand {
+    /// ピクセル数(`100`など)
+    Pixel(i64),
+    /// 元の大きさに対する百分率(`50%`など)
+    Percent(f64),
+    /// 元の大きさに対する倍率(`x1.5`など)
+    Scale(f64),
+}
+
+impl SizeCommand {
+    /// 元の大きさを元に、output_sizeに渡すピクセル数にする関数。
+    /// `MAX_OUTPUT_SIZE`を超える大きさが指定された場合はエラーにする。
+    fn to_pixels(self, input_size: NonZeroU32) -> Result<i64, ResizedPngError> {
+        let ratio = match self {
+            SizeCommand::Pixel(v) if v > MAX_OUTPUT_SIZE as i64 => {
+                return Err(ResizedPngError::InvalidSize)
+            }
+            SizeCommand::Pixel(v) => return Ok(v),
+            SizeCommand::Percent(v) => v / 100.0,
+            SizeCommand::Scale(v) => v,
+        };
+
+        let pixels = (input_size.get() as f64 * ratio).round();
+        if pixels > MAX_OUTPUT_SIZE as f64 {
+            return Err(ResizedPngError::InvalidSize);
+        }
+
+        // 0になると元のサイズ扱いになってしまうので、1以上にする。
+        Ok((pixels as i64).max(1))
+    }
+}
+
+impl FromStr for SizeCommand {
+    type Err = ResizedPngError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // 百分率と倍率は正の数のみ受け付ける。
+        let parse_ratio = |v: &str| match v.parse::<f64>() {
+            Ok(v) if v.is_finite() && v > 0.0 => Ok(v),
+            _ => Err(ResizedPngError::InvalidSize),
+        };
+
+        if let Some(v) = s.strip_suffix('%') {
+            parse_ratio(v).map(SizeCommand::Percent)
+        } else if let Some(v) = s.strip_prefix('x') {
+            parse_ratio(v).map(SizeCommand::Scale)
+        } else {
+            s.parse::<i64>()
+                .map(SizeCommand::Pixel)
+                .map_err(|_| ResizedPngError::InvalidSize)
+        }
+    }
+}
+
+/// 画像中の矩形範囲
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) struct Rect {
+    pub(crate) left: u32,
+    pub(crate) top: u32,
+    pub(crate) width: NonZeroU32,
+    pub(crate) height: NonZeroU32,
+}
+
+impl Rect {
+    /// 左上の位置と大きさの文字列から矩形範囲を読み取る関数。
+    pub(crate) fn from_strs(
+        left: &str,
+        top: &str,
+        width: &str,
+        height: &str,
+    ) -> Result<Rect, ResizedPngError> {
+        let parse_position = |v: &str| v.parse::<u32>().map_err(|_| ResizedPngError::InvalidRect);
+        let parse_size = |v: &str| {
+            v.parse::<NonZeroU32>()
+                .map_err(|_| ResizedPngError::InvalidRect)
+        };
+
+        Ok(Rect {
+            left: parse_position(left)?,
+            top: parse_position(top)?,
+            width: parse_size(width)?,
+            height: parse_size(height)?,
+        })
+    }
+
+    /// 矩形範囲が画像の内側に収まっているか確かめる関数。
+    pub(crate) fn check_inside(
+        &self,
+        image_width: NonZeroU32,
+        image_height: NonZeroU32,
+    ) -> Result<(), ResizedPngError> {
+        let right = self.left as u64 + self.width.get() as u64;
+        let bottom = self.top as u64 + self.height.get() as u64;
+
+        if right <= image_width.get() as u64 && bottom <= image_height.get() as u64 {
+            Ok(())
+        } else {
+            Err(ResizedPngError::InvalidRect)
+        }
+    }
+
+    fn to_crop_box(self) -> fir::CropBox {
+        fir::CropBox {
+            left: self.left,
+            top: self.top,
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+impl FromStr for Rect {
+    type Err = ResizedPngError;
+
+    /// `x,y,w,h`形式の文字列から読み取る。
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(',').map(|v| v.trim()).collect();
+
+        match parts[..] {
+            [left, top, width, height] => Rect::from_strs(left, top, width, height),
+            _ => Err(ResizedPngError::InvalidRect),
+        }
+    }
+}
+
+/// 出力画像の配置
+#[derive(Debug, PartialEq)]
+struct OutputLayout {
+    /// 入力画像のうち拡大縮小に使う範囲
+    src: Rect,
+    /// 拡大縮小後の横幅
+    resized_width: NonZeroU32,
+    /// 拡大縮小後の縦幅
+    resized_height: NonZeroU32,
+    /// 出力画像全体の横幅
+    canvas_width: NonZeroU32,
+    /// 出力画像全体の縦幅
+    canvas_height: NonZeroU32,
+    /// 拡大縮小後の画像を置く横方向の位置
+    offset_x: u32,
+    /// 拡大縮小後の画像を置く縦方向の位置
+    offset_y: u32,
+}
+
+fn output_layout(
+    output_width: NonZeroU32,
+    output_height: NonZeroU32,
+    input_width: NonZeroU32,
+    input_height: NonZeroU32,
+    mode: ResizeMode,
+    shrink_only: bool,
+) -> OutputLayout {
+    let full = Rect {
+        left: 0,
+        top: 0,
+        width: input_width,
+        height: input_height,
+    };
+    let ratio_x = output_width.get() as f64 / input_width.get() as f64;
+    let ratio_y = output_height.get() as f64 / input_height.get() as f64;
+
+    // shrink_onlyのときは拡大しない。
+    let limit_ratio = |ratio: f64| match shrink_only {
+        true => ratio.min(1.0),
+        false => ratio,
+    };
+
+    // 0になった場合は1にfallbackする。
+    let to_size = |v: f64| NonZeroU32::new(v.round() as u32).unwrap_or(NonZeroU32::new(1).unwrap());
+
+    match mode {
+        ResizeMode::Stretch => {
+            let (resized_width, resized_height) = match shrink_only {
+                true => (
+                    output_width.min(input_width),
+                    output_height.min(input_height),
+                ),
+                false => (output_width, output_height),
+            };
+
+            OutputLayout {
+                src: full,
+                resized_width,
+                resized_height,
+                canvas_width: resized_width,
+                canvas_height: resized_height,
+                offset_x: 0,
+                offset_y: 0,
+            }
+        }
+        ResizeMode::Fit | ResizeMode::Contain => {
+            let ratio = limit_ratio(ratio_x.min(ratio_y));
+            let resized_width = to_size(input_width.get() as f64 * ratio).min(output_width);
+            let resized_height = to_size(input_height.get() as f64 * ratio).min(output_height);
+
+            // Fitは余白を作らない。
+            let (canvas_width, canvas_height) = match mode {
+                ResizeMode::Contain => (output_width, output_height),
+                _ => (resized_width, resized_height),
+            };
+
+            OutputLayout {
+                src: full,
+                resized_width,
+                resized_height,
+                canvas_width,
+                canvas_height,
+                offset_x: (canvas_width.get() - resized_width.get()) / 2,
+                offset_y: (canvas_height.get() - resized_height.get()) / 2,
+            }
+        }
+        ResizeMode::Cover => {
+            let ratio = limit_ratio(ratio_x.max(ratio_y));
+            let src_width = to_size(output_width.get() as f64 / ratio).min(input_width);
+            let src_height = to_size(output_height.get() as f64 / ratio).min(input_height);
+
+            // 拡大しない場合は、切り取った範囲をそのまま使う。
+            let (resized_width, resized_height) = match ratio < ratio_x.max(ratio_y) {
+                true => (src_width, src_height),
+                false => (output_width, output_height),
+            };
+
+            OutputLayout {
+                src: Rect {
+                    left: (input_width.get() - src_width.get()) / 2,
+                    top: (input_height.get() - src_height.get()) / 2,
+                    width: src_width,
+                    height: src_height,
+                },
+                resized_width,
+                resized_height,
+                canvas_width: resized_width,
+                canvas_height: resized_height,
+                offset_x: 0,
+                offset_y: 0,
+            }
+        }
+    }
+}
+
+/// 拡大縮小後の画像を出力画像全体の大きさの背景に配置する関数。
+fn place_on_canvas(
+    resized_image: fir::Image<'static>,
+    layout: &OutputLayout,
+    background: [u8; 4],
+) -> fir::Image<'static> {
+    if layout.canvas_width == layout.resized_width && layout.canvas_height == layout.resized_height
+    {
+        return resized_image;
+    }
+
+    // 背景が透明な場合は、アルファ値を持つ色の種類にする。
+    let resized_image = if background[3] < 255 && !has_alpha(resized_image.pixel_type()) {
+        let color_type = color_type_with_alpha(resized_image.pixel_type());
+        to_fir_image(convert_color(to_dynamic_image(resized_image), color_type))
+            .expect("size of resized image is not 0")
+    } else {
+        resized_image
+    };
+
+    let background = pixel_bytes(background, resized_image.pixel_type());
+    let pixel_size = background.len();
+    let canvas_row_size = layout.canvas_width.get() as usize * pixel_size;
+    let resized_row_size = layout.resized_width.get() as usize * pixel_size;
+
+    let mut buffer =
+        background.repeat(layout.canvas_width.get() as usize * layout.canvas_height.get() as usize);
+
+    for (y, row) in resized_image
+        .buffer()
+        .chunks_exact(resized_row_size)
+        .enumerate()
+    {
+        let start = (layout.offset_y as usize + y) * canvas_row_size
+            + layout.offset_x as usize * pixel_size;
+        buffer[start..start + resized_row_size].copy_from_slice(row);
+    }
+
+    fir::Image::from_vec_u8(
+        layout.canvas_width,
+        layout.canvas_height,
+        buffer,
+        resized_image.pixel_type(),
+    )
+    .expect("buffer size is same as canvas")
 }
 
 fn output_size(
@@ -169,6 +901,8 @@ mod tests {
     mod to_resized_png {
         use super::*;
 
+        use crate::options::ResizeFilter;
+
         use tempfile::tempdir;
 
         #[test]
@@ -178,10 +912,19 @@ mod tests {
             let src_path =
                 PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.png");
             let dist_path = out_dir.path().join("from_png.png");
-            let width_command = 50;
-            let height_command = 100;
+            let width_command = SizeCommand::Pixel(50);
+            let height_command = SizeCommand::Pixel(100);
 
-            to_resized_png(&src_path, &dist_path, width_command, height_command).unwrap();
+            let options = ResizeOptions::default();
+
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                width_command,
+                height_command,
+                &options,
+            )
+            .unwrap();
 
             assert!(dist_path.exists());
 
@@ -195,10 +938,19 @@ mod tests {
             let src_path =
                 PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.webp");
             let dist_path = out_dir.path().join("from_webp.png");
-            let width_command = -1;
-            let height_command = 50;
+            let width_command = SizeCommand::Pixel(-1);
+            let height_command = SizeCommand::Pixel(50);
 
-            to_resized_png(&src_path, &dist_path, width_command, height_command).unwrap();
+            let options = ResizeOptions::default();
+
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                width_command,
+                height_command,
+                &options,
+            )
+            .unwrap();
 
             assert!(dist_path.exists());
 
@@ -212,10 +964,19 @@ mod tests {
             let src_path =
                 PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.bmp");
             let dist_path = out_dir.path().join("from_bmp.png");
-            let width_command = 50;
-            let height_command = -1;
+            let width_command = SizeCommand::Pixel(50);
+            let height_command = SizeCommand::Pixel(-1);
 
-            to_resized_png(&src_path, &dist_path, width_command, height_command).unwrap();
+            let options = ResizeOptions::default();
+
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                width_command,
+                height_command,
+                &options,
+            )
+            .unwrap();
 
             assert!(dist_path.exists());
 
@@ -229,64 +990,1673 @@ mod tests {
             let src_path =
                 PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.jpg");
             let dist_path = out_dir.path().join("from_jpg.png");
-            let width_command = 0;
-            let height_command = 0;
+            let width_command = SizeCommand::Pixel(0);
+            let height_command = SizeCommand::Pixel(0);
+
+            let options = ResizeOptions::default();
 
-            to_resized_png(&src_path, &dist_path, width_command, height_command).unwrap();
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                width_command,
+                height_command,
+                &options,
+            )
+            .unwrap();
 
             assert!(dist_path.exists());
 
             out_dir.close().unwrap();
         }
-    }
-
-    mod output_size {
-        use super::*;
 
         #[test]
-        fn none_when_both_width_and_height_are_minus() {
-            let width_command = -1;
-            let height_command = -1;
-            let input_width = NonZeroU32::new(100).unwrap();
-            let input_height = NonZeroU32::new(200).unwrap();
+        fn success_when_filter_is_specified() {
+            let out_dir = tempdir().unwrap();
 
-            assert!(
-                output_size(width_command, height_command, input_width, input_height).is_none()
-            );
-        }
+            let src_path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.png");
+            let dist_path = out_dir.path().join("nearest.png");
+            let width_command = SizeCommand::Pixel(25);
+            let height_command = SizeCommand::Pixel(-1);
+            let options = ResizeOptions {
+                filter: ResizeFilter::Nearest,
+                ..Default::default()
+            };
 
-        #[test]
-        fn original_value_when_width_and_height_are_0() {
-            let width_command = 0;
-            let height_command = 0;
-            let input_width = NonZeroU32::new(100).unwrap();
-            let input_height = NonZeroU32::new(200).unwrap();
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                width_command,
+                height_command,
+                &options,
+            )
+            .unwrap();
 
-            let (width, height) =
-                output_size(width_command, height_command, input_width, input_height).unwrap();
+            assert!(dist_path.exists());
 
-            assert_eq!(width, input_width);
-            assert_eq!(height, input_height);
+            out_dir.close().unwrap();
         }
 
         #[test]
-        fn keep_aspect_ratio_when_one_of_width_and_height_is_minus() {
-            let width_command = -1;
-            let height_command = 100;
-            let input_width = NonZeroU32::new(100).unwrap();
-            let input_height = NonZeroU32::new(200).unwrap();
+        fn failed_when_output_size_is_too_large() {
+            let out_dir = tempdir().unwrap();
 
-            let (width, height) =
-                output_size(width_command, height_command, input_width, input_height).unwrap();
+            let src_path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.png");
+            let dist_path = out_dir.path().join("too_large.png");
 
-            assert_eq!(width, NonZeroU32::new(50).unwrap());
-            assert_eq!(height, NonZeroU32::new(100).unwrap());
+            for (width_command, height_command) in [
+                (SizeCommand::Percent(100000000.0), SizeCommand::Pixel(-1)),
+                (SizeCommand::Scale(1e9), SizeCommand::Pixel(0)),
+                (SizeCommand::Pixel(-1), SizeCommand::Pixel(100000)),
+                (SizeCommand::Pixel(4294967396), SizeCommand::Pixel(50)),
+                (SizeCommand::Pixel(100), SizeCommand::Pixel(i64::MAX)),
+            ] {
+                let result = to_resized_png(
+                    &src_path,
+                    &dist_path,
+                    width_command,
+                    height_command,
+                    &Default::default(),
+                );
+
+                assert!(matches!(result, Err(ResizedPngError::InvalidSize)));
+                assert!(!dist_path.exists());
+            }
+
+            out_dir.close().unwrap();
         }
 
         #[test]
-        fn target_values_when_width_and_henght_are_not_0_and_minis() {
-            let width_command = 200;
-            let height_command = 300;
+        fn same_size_when_input_is_larger_than_max_output_size() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path = out_dir.path().join("large.png");
+            let dist_path = out_dir.path().join("output.png");
+            let large_width = MAX_OUTPUT_SIZE + 1;
+            DynamicImage::new_rgba8(large_width, 2)
+                .save(&src_path)
+                .unwrap();
+
+            for (width_command, height_command, expected) in [
+                (
+                    SizeCommand::Pixel(0),
+                    SizeCommand::Pixel(0),
+                    (large_width, 2),
+                ),
+                (
+                    SizeCommand::Pixel(-1),
+                    SizeCommand::Pixel(0),
+                    (large_width, 2),
+                ),
+                (
+                    SizeCommand::Pixel(0),
+                    SizeCommand::Pixel(1),
+                    (large_width, 1),
+                ),
+            ] {
+                to_resized_png(
+                    &src_path,
+                    &dist_path,
+                    width_command,
+                    height_command,
+                    &Default::default(),
+                )
+                .unwrap();
+
+                let output = image::open(&dist_path).unwrap();
+                assert_eq!((output.width(), output.height()), expected);
+            }
+
+            // 回転しても元の大きさのままなら出力できる。
+            let options = ResizeOptions {
+                rotation: Rotation::Rotate90,
+                ..Default::default()
+            };
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                SizeCommand::Pixel(0),
+                SizeCommand::Pixel(0),
+                &options,
+            )
+            .unwrap();
+
+            let output = image::open(&dist_path).unwrap();
+            assert_eq!((output.width(), output.height()), (2, large_width));
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod to_resized_image {
+        use super::*;
+
+        use tempfile::tempdir;
+
+        fn output_format(path: &PathBuf) -> Option<image::ImageFormat> {
+            ImageReader::open(path)
+                .unwrap()
+                .with_guessed_format()
+                .unwrap()
+                .format()
+        }
+
+        #[test]
+        fn format_of_extension_when_format_is_not_specified() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.png");
+            let dist_path = out_dir.path().join("output.webp");
+
+            to_resized_image(
+                &src_path,
+                &dist_path,
+                SizeCommand::Pixel(30),
+                SizeCommand::Pixel(-1),
+                &ResizeOptions::default(),
+            )
+            .unwrap();
+
+            assert_eq!(output_format(&dist_path), Some(image::ImageFormat::WebP));
+            assert_eq!(image::open(&dist_path).unwrap().width(), 30);
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn specified_format_when_format_is_specified() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.png");
+            let dist_path = out_dir.path().join("output.png");
+            let options = ResizeOptions {
+                format: Some(OutputFormat::Jpeg),
+                quality: 90,
+                ..Default::default()
+            };
+
+            to_resized_image(
+                &src_path,
+                &dist_path,
+                SizeCommand::Pixel(30),
+                SizeCommand::Pixel(-1),
+                &options,
+            )
+            .unwrap();
+
+            assert_eq!(output_format(&dist_path), Some(image::ImageFormat::Jpeg));
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn png_when_called_as_to_resized_png() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.png");
+            let dist_path = out_dir.path().join("output.bmp");
+            let options = ResizeOptions {
+                format: Some(OutputFormat::Jpeg),
+                ..Default::default()
+            };
+
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                SizeCommand::Pixel(30),
+                SizeCommand::Pixel(-1),
+                &options,
+            )
+            .unwrap();
+
+            assert_eq!(output_format(&dist_path), Some(image::ImageFormat::Png));
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn failed_when_extension_is_unknown() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.png");
+            let dist_path = out_dir.path().join("output.txt");
+
+            assert!(matches!(
+                to_resized_image(
+                    &src_path,
+                    &dist_path,
+                    SizeCommand::Pixel(30),
+                    SizeCommand::Pixel(-1),
+                    &ResizeOptions::default(),
+                ),
+                Err(ResizedPngError::Unsupported)
+            ));
+            assert!(!dist_path.exists());
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod to_resized_png_with_mode {
+        use super::*;
+
+        use tempfile::tempdir;
+
+        #[test]
+        fn target_size_when_mode_is_contain() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.png");
+            let dist_path = out_dir.path().join("contain.png");
+            let width_command = SizeCommand::Pixel(80);
+            let height_command = SizeCommand::Pixel(30);
+            let options = ResizeOptions {
+                mode: ResizeMode::Contain,
+                background: [255, 255, 255, 255],
+                ..Default::default()
+            };
+
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                width_command,
+                height_command,
+                &options,
+            )
+            .unwrap();
+
+            let output = image::open(&dist_path).unwrap();
+            assert_eq!((output.width(), output.height()), (80, 30));
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod to_resized_png_with_shrink_only {
+        use super::*;
+
+        use tempfile::tempdir;
+
+        #[test]
+        fn same_pixels_when_input_is_within_target() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.png");
+            let dist_path = out_dir.path().join("shrink_only.png");
+            let width_command = SizeCommand::Scale(4.0);
+            let height_command = SizeCommand::Pixel(-1);
+            let options = ResizeOptions {
+                shrink_only: true,
+                ..Default::default()
+            };
+
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                width_command,
+                height_command,
+                &options,
+            )
+            .unwrap();
+
+            let input = image::open(&src_path).unwrap().to_rgba8();
+            let output = image::open(&dist_path).unwrap().to_rgba8();
+            assert_eq!(input, output);
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod to_resized_png_with_crop {
+        use super::*;
+
+        use tempfile::tempdir;
+
+        #[test]
+        fn cropped_pixels_when_crop_before_is_specified() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.png");
+            let dist_path = out_dir.path().join("crop_before.png");
+            let width_command = SizeCommand::Pixel(0);
+            let height_command = SizeCommand::Pixel(0);
+            let options = ResizeOptions {
+                crop_before: Some("3,4,10,20".parse().unwrap()),
+                ..Default::default()
+            };
+
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                width_command,
+                height_command,
+                &options,
+            )
+            .unwrap();
+
+            let input = image::open(&src_path).unwrap().to_rgba8();
+            let output = image::open(&dist_path).unwrap().to_rgba8();
+            assert_eq!((output.width(), output.height()), (10, 20));
+            assert_eq!(output.get_pixel(0, 0), input.get_pixel(3, 4));
+            assert_eq!(output.get_pixel(9, 19), input.get_pixel(12, 23));
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn cropped_size_when_crop_after_is_specified() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.png");
+            let dist_path = out_dir.path().join("crop_after.png");
+            let width_command = SizeCommand::Pixel(40);
+            let height_command = SizeCommand::Pixel(40);
+            let options = ResizeOptions {
+                crop_after: Some("10,10,20,30".parse().unwrap()),
+                ..Default::default()
+            };
+
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                width_command,
+                height_command,
+                &options,
+            )
+            .unwrap();
+
+            let output = image::open(&dist_path).unwrap();
+            assert_eq!((output.width(), output.height()), (20, 30));
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn failed_when_rect_is_outside_of_image() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.png");
+            let dist_path = out_dir.path().join("crop_outside.png");
+            let width_command = SizeCommand::Pixel(40);
+            let height_command = SizeCommand::Pixel(40);
+            let options = ResizeOptions {
+                crop_after: Some("30,0,20,20".parse().unwrap()),
+                ..Default::default()
+            };
+
+            let result = to_resized_png(
+                &src_path,
+                &dist_path,
+                width_command,
+                height_command,
+                &options,
+            );
+
+            assert!(matches!(result, Err(ResizedPngError::InvalidRect)));
+            assert!(!dist_path.exists());
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod crop_to_png {
+        use super::*;
+
+        use tempfile::tempdir;
+
+        #[test]
+        fn rect_size_when_rect_is_inside_of_image() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.bmp");
+            let dist_path = out_dir.path().join("crop.png");
+            let rect = Rect::from_strs("0", "0", "8", "6").unwrap();
+
+            crop_to_png(&src_path, &dist_path, rect).unwrap();
+
+            let output = image::open(&dist_path).unwrap();
+            assert_eq!((output.width(), output.height()), (8, 6));
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn failed_when_rect_is_outside_of_image() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.bmp");
+            let dist_path = out_dir.path().join("crop.png");
+            let rect = Rect::from_strs("0", "0", "100000", "6").unwrap();
+
+            assert!(matches!(
+                crop_to_png(&src_path, &dist_path, rect),
+                Err(ResizedPngError::InvalidRect)
+            ));
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod to_resized_png_with_exif_orientation {
+        use super::*;
+
+        use image::Rgba;
+        use tempfile::tempdir;
+
+        fn is_near(pixel: &Rgba<u8>, expected: [u8; 3]) -> bool {
+            pixel
+                .0
+                .iter()
+                .zip(expected.iter())
+                .all(|(a, b)| (*a as i16 - *b as i16).abs() < 32)
+        }
+
+        #[test]
+        fn upright_image_when_each_orientation() {
+            let out_dir = tempdir().unwrap();
+
+            for index in 1..=8 {
+                let src_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(format!(
+                    "test_target/image/sample_orientation_{}.jpg",
+                    index
+                ));
+                let dist_path = out_dir.path().join(format!("orientation_{}.png", index));
+                let width_command = SizeCommand::Pixel(0);
+                let height_command = SizeCommand::Pixel(0);
+                let options = ResizeOptions::default();
+
+                to_resized_png(
+                    &src_path,
+                    &dist_path,
+                    width_command,
+                    height_command,
+                    &options,
+                )
+                .unwrap();
+
+                let output = image::open(&dist_path).unwrap().to_rgba8();
+                assert_eq!(output.dimensions(), (24, 16), "{}", index);
+                assert!(is_near(output.get_pixel(2, 2), [255, 0, 0]), "{}", index);
+                assert!(is_near(output.get_pixel(21, 2), [0, 255, 0]), "{}", index);
+                assert!(is_near(output.get_pixel(2, 13), [0, 0, 255]), "{}", index);
+                assert!(
+                    is_near(output.get_pixel(21, 13), [255, 255, 255]),
+                    "{}",
+                    index
+                );
+            }
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn stored_image_when_exif_orientation_is_disabled() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("test_target/image/sample_orientation_6.jpg");
+            let dist_path = out_dir.path().join("orientation_disabled.png");
+            let width_command = SizeCommand::Pixel(0);
+            let height_command = SizeCommand::Pixel(0);
+            let options = ResizeOptions {
+                exif_orientation: false,
+                ..Default::default()
+            };
+
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                width_command,
+                height_command,
+                &options,
+            )
+            .unwrap();
+
+            let output = image::open(&dist_path).unwrap();
+            assert_eq!((output.width(), output.height()), (16, 24));
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod to_resized_png_with_pna {
+        use super::*;
+
+        use tempfile::tempdir;
+
+        #[test]
+        fn rgb_png_and_gray_pna_when_pna_is_enabled() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.png");
+            let dist_path = out_dir.path().join("surface0.png");
+            let width_command = SizeCommand::Pixel(0);
+            let height_command = SizeCommand::Pixel(0);
+            let options = ResizeOptions {
+                pna: true,
+                ..Default::default()
+            };
+
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                width_command,
+                height_command,
+                &options,
+            )
+            .unwrap();
+
+            let input = image::open(&src_path).unwrap().to_rgba8();
+            let output = image::open(&dist_path).unwrap();
+            let output_pna = ImageReader::open(out_dir.path().join("surface0.pna"))
+                .unwrap()
+                .with_guessed_format()
+                .unwrap()
+                .decode()
+                .unwrap();
+
+            assert_eq!(output.color(), image::ColorType::Rgb8);
+            assert_eq!(output_pna.color(), image::ColorType::L8);
+            assert_eq!(
+                output_pna.to_luma8().into_raw(),
+                input.pixels().map(|p| p.0[3]).collect::<Vec<u8>>()
+            );
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod to_resized_png_with_metadata {
+        use super::*;
+
+        use std::{borrow::Cow, fs::File};
+
+        use tempfile::tempdir;
+
+        /// 付加情報を持つpngを作る。
+        fn prepare_png_with_metadata(path: &Path) {
+            let mut info = png::Info::with_size(4, 4);
+            info.color_type = png::ColorType::Rgba;
+            info.bit_depth = png::BitDepth::Eight;
+            info.icc_profile = Some(Cow::Owned(vec![1, 2, 3, 4]));
+            info.source_gamma = Some(png::ScaledFloat::new(0.45455));
+            info.pixel_dims = Some(png::PixelDimensions {
+                xppu: 3780,
+                yppu: 3780,
+                unit: png::Unit::Meter,
+            });
+
+            let mut encoder = png::Encoder::with_info(File::create(path).unwrap(), info).unwrap();
+            encoder
+                .add_text_chunk(String::from("Author"), String::from("tukinami"))
+                .unwrap();
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[255; 4 * 4 * 4]).unwrap();
+            writer.finish().unwrap();
+        }
+
+        fn read_info(path: &Path) -> png::Info<'static> {
+            let reader = png::Decoder::new(File::open(path).unwrap())
+                .read_info()
+                .unwrap();
+            let info = reader.info();
+
+            let mut copied = png::Info::default();
+            copied.icc_profile = info.icc_profile.as_ref().map(|v| Cow::Owned(v.to_vec()));
+            copied.source_gamma = info.source_gamma;
+            copied.pixel_dims = info.pixel_dims;
+            copied.uncompressed_latin1_text = info.uncompressed_latin1_text.clone();
+            copied.utf8_text = info.utf8_text.clone();
+
+            copied
+        }
+
+        fn resize_with_options(options: &ResizeOptions) -> png::Info<'static> {
+            let out_dir = tempdir().unwrap();
+
+            let src_path = out_dir.path().join("input.png");
+            let dist_path = out_dir.path().join("output.png");
+            prepare_png_with_metadata(&src_path);
+
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                SizeCommand::Pixel(2),
+                SizeCommand::Pixel(2),
+                options,
+            )
+            .unwrap();
+
+            let info = read_info(&dist_path);
+            out_dir.close().unwrap();
+
+            info
+        }
+
+        #[test]
+        fn same_metadata_when_metadata_is_keep() {
+            let options = ResizeOptions {
+                metadata: MetadataMode::Keep,
+                ..Default::default()
+            };
+
+            let info = resize_with_options(&options);
+
+            assert_eq!(info.icc_profile.as_deref(), Some([1, 2, 3, 4].as_slice()));
+            assert_eq!(info.source_gamma, Some(png::ScaledFloat::new(0.45455)));
+            let pixel_dims = info.pixel_dims.unwrap();
+            assert_eq!((pixel_dims.xppu, pixel_dims.yppu), (3780, 3780));
+            assert_eq!(info.uncompressed_latin1_text.len(), 1);
+            assert_eq!(info.uncompressed_latin1_text[0].keyword, "Author");
+            assert_eq!(info.uncompressed_latin1_text[0].text, "tukinami");
+        }
+
+        #[test]
+        fn icc_profile_chunk_written_when_metadata_is_keep() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path = out_dir.path().join("input.png");
+            let dist_path = out_dir.path().join("output.png");
+            prepare_png_with_metadata(&src_path);
+            let options = ResizeOptions {
+                metadata: MetadataMode::Keep,
+                ..Default::default()
+            };
+
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                SizeCommand::Pixel(2),
+                SizeCommand::Pixel(2),
+                &options,
+            )
+            .unwrap();
+
+            // デコーダを通さずに、チャンクの種類を順に読む。
+            let bytes = std::fs::read(&dist_path).unwrap();
+            let mut chunk_types = Vec::new();
+            let mut offset = 8;
+            while offset + 8 <= bytes.len() {
+                let length =
+                    u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize;
+                chunk_types.push(bytes[offset + 4..offset + 8].to_vec());
+                offset += 12 + length;
+            }
+
+            let icc_index = chunk_types.iter().position(|v| v == b"iCCP").unwrap();
+            let idat_index = chunk_types.iter().position(|v| v == b"IDAT").unwrap();
+            assert!(icc_index < idat_index);
+            assert!(!chunk_types.iter().any(|v| v == b"sRGB"));
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn no_metadata_when_metadata_is_strip() {
+            let info = resize_with_options(&ResizeOptions::default());
+
+            assert!(info.icc_profile.is_none());
+            assert!(info.source_gamma.is_none());
+            assert!(info.pixel_dims.is_none());
+            assert!(info.uncompressed_latin1_text.is_empty());
+        }
+
+        #[test]
+        fn added_texts_when_texts_are_specified() {
+            let options = ResizeOptions {
+                texts: vec![
+                    (String::from("Title"), String::from("surface0")),
+                    (String::from("Ghost"), String::from("ゴースト")),
+                ],
+                ..Default::default()
+            };
+
+            let info = resize_with_options(&options);
+
+            assert_eq!(info.uncompressed_latin1_text.len(), 1);
+            assert_eq!(info.uncompressed_latin1_text[0].keyword, "Title");
+            assert_eq!(info.uncompressed_latin1_text[0].text, "surface0");
+            assert_eq!(info.utf8_text.len(), 1);
+            assert_eq!(info.utf8_text[0].keyword, "Ghost");
+            assert_eq!(info.utf8_text[0].get_text().unwrap(), "ゴースト");
+        }
+    }
+
+    mod to_resized_png_with_pna_and_colors {
+        use super::*;
+
+        use tempfile::tempdir;
+
+        #[test]
+        fn gray_pna_when_colors_is_specified() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.png");
+            let dist_path = out_dir.path().join("output.png");
+            let options = ResizeOptions {
+                pna: true,
+                colors: Some(16),
+                ..Default::default()
+            };
+
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                SizeCommand::Pixel(20),
+                SizeCommand::Pixel(20),
+                &options,
+            )
+            .unwrap();
+
+            let alpha = ImageReader::open(pna_path(&dist_path))
+                .unwrap()
+                .with_guessed_format()
+                .unwrap()
+                .decode()
+                .unwrap();
+            assert_eq!(alpha.color(), image::ColorType::L8);
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod to_resized_image_with_animation {
+        use super::*;
+
+        use image::codecs::gif::GifEncoder;
+        use image::{Frame, Rgba, RgbaImage};
+        use tempfile::tempdir;
+
+        /// 色の異なる3つのフレームを持つgifを作る。
+        fn prepare_animated_gif(dir: &Path) -> PathBuf {
+            let src_path = dir.join("animated.gif");
+            let mut encoder = GifEncoder::new(std::fs::File::create(&src_path).unwrap());
+            for (i, color) in [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]]
+                .iter()
+                .enumerate()
+            {
+                let buffer = RgbaImage::from_pixel(8, 8, Rgba(*color));
+                let delay = Delay::from_numer_denom_ms(100 * (i as u32 + 1), 1);
+                encoder
+                    .encode_frame(Frame::from_parts(buffer, 0, 0, delay))
+                    .unwrap();
+            }
+
+            src_path
+        }
+
+        fn resize_animation(src_path: &PathBuf, dist_path: &Path, animation: AnimationMode) {
+            let options = ResizeOptions {
+                animation,
+                ..Default::default()
+            };
+
+            to_resized_image(
+                src_path,
+                dist_path,
+                SizeCommand::Pixel(4),
+                SizeCommand::Pixel(4),
+                &options,
+            )
+            .unwrap();
+        }
+
+        #[test]
+        fn all_frames_and_delays_when_animation_is_apng() {
+            let out_dir = tempdir().unwrap();
+            let src_path = prepare_animated_gif(out_dir.path());
+            let dist_path = out_dir.path().join("output.png");
+
+            resize_animation(&src_path, &dist_path, AnimationMode::Apng);
+
+            let decoder = png::Decoder::new(std::fs::File::open(&dist_path).unwrap());
+            let mut reader = decoder.read_info().unwrap();
+            assert_eq!(reader.info().animation_control.unwrap().num_frames, 3);
+            assert_eq!((reader.info().width, reader.info().height), (4, 4));
+
+            let mut buffer = vec![0; reader.output_buffer_size()];
+            for (i, color) in [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]]
+                .iter()
+                .enumerate()
+            {
+                reader.next_frame(&mut buffer).unwrap();
+                let control = reader.info().frame_control.unwrap();
+                assert_eq!(
+                    (control.delay_num, control.delay_den),
+                    (100 * (i as u16 + 1), 1000)
+                );
+                assert_eq!(&buffer[..4], color);
+            }
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn numbered_files_when_animation_is_split() {
+            let out_dir = tempdir().unwrap();
+            let src_path = prepare_animated_gif(out_dir.path());
+            let dist_path = out_dir.path().join("frame_{}.png");
+
+            resize_animation(&src_path, &dist_path, AnimationMode::Split);
+
+            for (i, color) in [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]]
+                .iter()
+                .enumerate()
+            {
+                let output = image::open(out_dir.path().join(format!("frame_{}.png", i)))
+                    .unwrap()
+                    .to_rgba8();
+                assert_eq!(output.dimensions(), (4, 4));
+                assert_eq!(&output.get_pixel(0, 0).0, color);
+            }
+            assert!(!out_dir.path().join("frame_3.png").exists());
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn single_frame_when_image_is_not_animated() {
+            let out_dir = tempdir().unwrap();
+            let src_path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.png");
+            let dist_path = out_dir.path().join("frame_{}.png");
+
+            resize_animation(&src_path, &dist_path, AnimationMode::Split);
+
+            assert!(out_dir.path().join("frame_0.png").exists());
+            assert!(!out_dir.path().join("frame_1.png").exists());
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn specified_frame_when_frame_is_specified() {
+            let out_dir = tempdir().unwrap();
+            let src_path = prepare_animated_gif(out_dir.path());
+            let dist_path = out_dir.path().join("output.png");
+            let options = ResizeOptions {
+                frame: Some(1),
+                ..Default::default()
+            };
+
+            to_resized_image(
+                &src_path,
+                &dist_path,
+                SizeCommand::Pixel(4),
+                SizeCommand::Pixel(4),
+                &options,
+            )
+            .unwrap();
+
+            let output = image::open(&dist_path).unwrap().to_rgba8();
+            assert_eq!(output.dimensions(), (4, 4));
+            assert_eq!(output.get_pixel(0, 0).0, [0, 255, 0, 255]);
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn failed_when_frame_is_out_of_range() {
+            let out_dir = tempdir().unwrap();
+            let src_path = prepare_animated_gif(out_dir.path());
+            let dist_path = out_dir.path().join("output.png");
+            let options = ResizeOptions {
+                frame: Some(3),
+                ..Default::default()
+            };
+
+            let result = to_resized_image(
+                &src_path,
+                &dist_path,
+                SizeCommand::Pixel(4),
+                SizeCommand::Pixel(4),
+                &options,
+            );
+
+            assert!(matches!(result, Err(ResizedPngError::InvalidOption)));
+            assert!(!dist_path.exists());
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn failed_when_split_path_has_no_pattern() {
+            let out_dir = tempdir().unwrap();
+            let src_path = prepare_animated_gif(out_dir.path());
+            let dist_path = out_dir.path().join("frame.png");
+            let options = ResizeOptions {
+                animation: AnimationMode::Split,
+                ..Default::default()
+            };
+
+            let result = to_resized_image(
+                &src_path,
+                &dist_path,
+                SizeCommand::Pixel(4),
+                SizeCommand::Pixel(4),
+                &options,
+            );
+
+            assert!(matches!(result, Err(ResizedPngError::InvalidOption)));
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn failed_when_apng_is_not_png() {
+            let out_dir = tempdir().unwrap();
+            let src_path = prepare_animated_gif(out_dir.path());
+            let dist_path = out_dir.path().join("output.webp");
+            let options = ResizeOptions {
+                animation: AnimationMode::Apng,
+                ..Default::default()
+            };
+
+            let result = to_resized_image(
+                &src_path,
+                &dist_path,
+                SizeCommand::Pixel(4),
+                SizeCommand::Pixel(4),
+                &options,
+            );
+
+            assert!(matches!(result, Err(ResizedPngError::Unsupported)));
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod to_resized_png_with_trim {
+        use super::*;
+
+        use image::codecs::gif::GifEncoder;
+        use image::{Frame, Rgba, RgbaImage};
+        use tempfile::tempdir;
+
+        #[test]
+        fn trimmed_size_when_trim_is_specified() {
+            let out_dir = tempdir().unwrap();
+            let src_path = out_dir.path().join("input.png");
+            let mut image = RgbaImage::from_pixel(20, 20, Rgba([0, 0, 0, 0]));
+            for (x, y) in [(5, 4), (12, 9)] {
+                image.put_pixel(x, y, Rgba([255, 0, 0, 255]));
+            }
+            image.save(&src_path).unwrap();
+            let dist_path = out_dir.path().join("output.png");
+            let options = ResizeOptions {
+                trim: Some(1),
+                ..Default::default()
+            };
+
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                SizeCommand::Pixel(0),
+                SizeCommand::Pixel(0),
+                &options,
+            )
+            .unwrap();
+
+            let output = image::open(&dist_path).unwrap().to_rgba8();
+            assert_eq!(output.dimensions(), (8 + 2, 6 + 2));
+            assert_eq!(output.get_pixel(1, 1).0, [255, 0, 0, 255]);
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn same_size_of_all_frames_when_opaque_areas_are_different() {
+            let out_dir = tempdir().unwrap();
+            let src_path = out_dir.path().join("input.gif");
+            let mut encoder = GifEncoder::new(std::fs::File::create(&src_path).unwrap());
+            for (x, y) in [(5, 4), (12, 9)] {
+                let mut image = RgbaImage::from_pixel(20, 20, Rgba([0, 0, 0, 0]));
+                image.put_pixel(x, y, Rgba([255, 0, 0, 255]));
+                encoder
+                    .encode_frame(Frame::from_parts(
+                        image,
+                        0,
+                        0,
+                        Delay::from_numer_denom_ms(100, 1),
+                    ))
+                    .unwrap();
+            }
+            drop(encoder);
+            let dist_path = out_dir.path().join("frame_{}.png");
+            let options = ResizeOptions {
+                animation: AnimationMode::Split,
+                trim: Some(0),
+                ..Default::default()
+            };
+
+            to_resized_image(
+                &src_path,
+                &dist_path,
+                SizeCommand::Pixel(0),
+                SizeCommand::Pixel(0),
+                &options,
+            )
+            .unwrap();
+
+            // すべてのフレームの不透明な画素を囲む範囲で切り取る。
+            let first = image::open(out_dir.path().join("frame_0.png"))
+                .unwrap()
+                .to_rgba8();
+            let second = image::open(out_dir.path().join("frame_1.png"))
+                .unwrap()
+                .to_rgba8();
+            assert_eq!(first.dimensions(), (8, 6));
+            assert_eq!(second.dimensions(), (8, 6));
+            assert_eq!(first.get_pixel(0, 0).0, [255, 0, 0, 255]);
+            assert_eq!(second.get_pixel(7, 5).0, [255, 0, 0, 255]);
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod to_resized_png_with_read_pna {
+        use super::*;
+
+        use image::{GrayImage, Luma, Rgb, RgbImage};
+        use tempfile::tempdir;
+
+        fn prepare_surface(dir: &std::path::Path) -> PathBuf {
+            let src_path = dir.join("surface0.png");
+            RgbImage::from_pixel(4, 4, Rgb([10, 20, 30]))
+                .save_with_format(&src_path, image::ImageFormat::Png)
+                .unwrap();
+            GrayImage::from_fn(4, 4, |x, _| Luma([if x < 2 { 0 } else { 255 }]))
+                .save_with_format(dir.join("surface0.pna"), image::ImageFormat::Png)
+                .unwrap();
+
+            src_path
+        }
+
+        #[test]
+        fn alpha_from_pna_when_pna_exists() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path = prepare_surface(out_dir.path());
+            let dist_path = out_dir.path().join("output.png");
+            let width_command = SizeCommand::Pixel(0);
+            let height_command = SizeCommand::Pixel(0);
+            let options = ResizeOptions::default();
+
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                width_command,
+                height_command,
+                &options,
+            )
+            .unwrap();
+
+            let output = image::open(&dist_path).unwrap().to_rgba8();
+            assert_eq!(output.get_pixel(0, 0).0[3], 0);
+            assert_eq!(output.get_pixel(3, 0).0[3], 255);
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn opaque_when_read_pna_is_disabled() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path = prepare_surface(out_dir.path());
+            let dist_path = out_dir.path().join("output.png");
+            let width_command = SizeCommand::Pixel(0);
+            let height_command = SizeCommand::Pixel(0);
+            let options = ResizeOptions {
+                read_pna: false,
+                ..Default::default()
+            };
+
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                width_command,
+                height_command,
+                &options,
+            )
+            .unwrap();
+
+            let output = image::open(&dist_path).unwrap().to_rgba8();
+            assert!(output.pixels().all(|p| p.0[3] == 255));
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod to_resized_png_with_color_key {
+        use super::*;
+
+        use image::{Rgb, RgbImage};
+        use tempfile::tempdir;
+
+        #[test]
+        fn no_fringe_of_key_color_when_color_key_is_enabled() {
+            let out_dir = tempdir().unwrap();
+
+            // 緑を透明色とし、中央に赤い四角を置いた画像
+            let src_path = out_dir.path().join("surface0.bmp");
+            let image = RgbImage::from_fn(16, 16, |x, y| match (x, y) {
+                (4..=11, 4..=11) => Rgb([255, 0, 0]),
+                _ => Rgb([0, 255, 0]),
+            });
+            image
+                .save_with_format(&src_path, image::ImageFormat::Bmp)
+                .unwrap();
+            let dist_path = out_dir.path().join("output.png");
+            let width_command = SizeCommand::Pixel(7);
+            let height_command = SizeCommand::Pixel(7);
+            let options = ResizeOptions {
+                color_key: true,
+                ..Default::default()
+            };
+
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                width_command,
+                height_command,
+                &options,
+            )
+            .unwrap();
+
+            let output = image::open(&dist_path).unwrap().to_rgba8();
+            assert_eq!(output.get_pixel(0, 0).0[3], 0);
+            assert_eq!(output.get_pixel(3, 3).0[..3], [255, 0, 0]);
+            assert!(output.pixels().filter(|p| p.0[3] > 0).all(|p| p.0[1] == 0));
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod to_resized_png_with_linear_light {
+        use super::*;
+
+        use image::{GrayImage, Luma};
+        use tempfile::tempdir;
+
+        /// 1画素ごとの白黒の市松模様を縮小したときの平均輝度
+        fn mean_of_downscaled_checkerboard(linear_light: bool) -> f64 {
+            let out_dir = tempdir().unwrap();
+
+            let src_path = out_dir.path().join("checkerboard.png");
+            GrayImage::from_fn(64, 64, |x, y| {
+                Luma([if (x + y) % 2 == 0 { 0 } else { 255 }])
+            })
+            .save(&src_path)
+            .unwrap();
+            let dist_path = out_dir.path().join("output.png");
+            let width_command = SizeCommand::Pixel(16);
+            let height_command = SizeCommand::Pixel(16);
+            let options = ResizeOptions {
+                linear_light,
+                ..Default::default()
+            };
+
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                width_command,
+                height_command,
+                &options,
+            )
+            .unwrap();
+
+            let output = image::open(&dist_path).unwrap().to_luma8();
+            let sum: u64 = output.pixels().map(|p| p.0[0] as u64).sum();
+
+            out_dir.close().unwrap();
+
+            sum as f64 / (output.width() * output.height()) as f64
+        }
+
+        #[test]
+        fn brighter_mean_when_linear_light_is_enabled() {
+            let srgb_mean = mean_of_downscaled_checkerboard(false);
+            let linear_mean = mean_of_downscaled_checkerboard(true);
+
+            // sRGBのままでは中間の128付近、リニアでは明るさ50%に当たる188付近になる。
+            assert!((srgb_mean - 127.5).abs() < 4.0, "{}", srgb_mean);
+            assert!((linear_mean - 187.5).abs() < 4.0, "{}", linear_mean);
+        }
+    }
+
+    mod to_resized_png_with_color_type {
+        use super::*;
+
+        use image::{ImageBuffer, Luma, Rgb, Rgba};
+        use tempfile::tempdir;
+
+        #[test]
+        fn same_pixels_when_input_is_16bit() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path = out_dir.path().join("input.png");
+            let dist_path = out_dir.path().join("output.png");
+            let input: ImageBuffer<Rgba<u16>, Vec<u16>> = ImageBuffer::from_fn(4, 3, |x, y| {
+                Rgba([x as u16 * 10001, y as u16 * 3, 257, 65535])
+            });
+            input.save(&src_path).unwrap();
+
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                SizeCommand::Pixel(0),
+                SizeCommand::Pixel(0),
+                &ResizeOptions::default(),
+            )
+            .unwrap();
+
+            let output = image::open(&dist_path).unwrap();
+            assert_eq!(output.color(), image::ColorType::Rgba16);
+            assert_eq!(output.to_rgba16(), input);
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn same_color_type_when_input_is_gray() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path = out_dir.path().join("input.png");
+            let dist_path = out_dir.path().join("output.png");
+            let input: ImageBuffer<Luma<u16>, Vec<u16>> =
+                ImageBuffer::from_fn(8, 8, |x, y| Luma([(x * y * 1000) as u16]));
+            input.save(&src_path).unwrap();
+
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                SizeCommand::Pixel(4),
+                SizeCommand::Pixel(4),
+                &ResizeOptions::default(),
+            )
+            .unwrap();
+
+            let output = image::open(&dist_path).unwrap();
+            assert_eq!(output.color(), image::ColorType::L16);
+            assert_eq!((output.width(), output.height()), (4, 4));
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn same_color_type_when_input_is_gray_and_linear_light_is_enabled() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path = out_dir.path().join("input.png");
+            let dist_path = out_dir.path().join("output.png");
+            image::GrayImage::from_pixel(8, 8, Luma([128]))
+                .save(&src_path)
+                .unwrap();
+            let options = ResizeOptions {
+                linear_light: true,
+                ..Default::default()
+            };
+
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                SizeCommand::Pixel(4),
+                SizeCommand::Pixel(4),
+                &options,
+            )
+            .unwrap();
+
+            let output = image::open(&dist_path).unwrap();
+            assert_eq!(output.color(), image::ColorType::L8);
+            assert!(output
+                .to_luma8()
+                .pixels()
+                .all(|p| p.0[0].abs_diff(128) <= 1));
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn specified_color_type_when_color_type_is_specified() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.png");
+            let dist_path = out_dir.path().join("output.png");
+            let options = ResizeOptions {
+                color_type: Some(image::ColorType::La8),
+                ..Default::default()
+            };
+
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                SizeCommand::Pixel(20),
+                SizeCommand::Pixel(20),
+                &options,
+            )
+            .unwrap();
+
+            let output = image::open(&dist_path).unwrap();
+            assert_eq!(output.color(), image::ColorType::La8);
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn with_alpha_when_padded_with_transparent_background() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path = out_dir.path().join("input.png");
+            let dist_path = out_dir.path().join("output.png");
+            image::RgbImage::from_pixel(10, 10, Rgb([255, 0, 0]))
+                .save(&src_path)
+                .unwrap();
+            let options = ResizeOptions {
+                mode: ResizeMode::Contain,
+                ..Default::default()
+            };
+
+            to_resized_png(
+                &src_path,
+                &dist_path,
+                SizeCommand::Pixel(20),
+                SizeCommand::Pixel(10),
+                &options,
+            )
+            .unwrap();
+
+            let output = image::open(&dist_path).unwrap();
+            assert_eq!(output.color(), image::ColorType::Rgba8);
+            let output = output.to_rgba8();
+            assert_eq!(output.get_pixel(0, 5).0, [0, 0, 0, 0]);
+            assert_eq!(output.get_pixel(10, 5).0, [255, 0, 0, 255]);
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod resize_image {
+        use super::*;
+
+        use crate::options::ResizeFilter;
+
+        fn size(v: u32) -> NonZeroU32 {
+            NonZeroU32::new(v).unwrap()
+        }
+
+        fn resize_pixels(
+            pixels: &[[u8; 4]],
+            input_width: u32,
+            output_width: u32,
+            options: &ResizeOptions,
+        ) -> Vec<[u8; 4]> {
+            let input_height = pixels.len() as u32 / input_width;
+            let mut input_image = fir::Image::from_vec_u8(
+                size(input_width),
+                size(input_height),
+                pixels.concat(),
+                fir::PixelType::U8x4,
+            )
+            .unwrap();
+            let layout = output_layout(
+                size(output_width),
+                size(input_height),
+                size(input_width),
+                size(input_height),
+                ResizeMode::Stretch,
+                false,
+            );
+
+            let output_image = resize_image(&mut input_image, &layout, options);
+
+            output_image
+                .buffer()
+                .chunks_exact(4)
+                .map(|v| [v[0], v[1], v[2], v[3]])
+                .collect()
+        }
+
+        #[test]
+        fn golden_value_when_alpha_is_premultiplied() {
+            let pixels = [[255, 0, 0, 255], [255, 255, 255, 0]];
+            let options = ResizeOptions {
+                filter: ResizeFilter::Box,
+                ..Default::default()
+            };
+
+            assert_eq!(resize_pixels(&pixels, 2, 1, &options), [[255, 0, 0, 128]]);
+        }
+
+        #[test]
+        fn golden_value_when_alpha_is_straight() {
+            let pixels = [[255, 0, 0, 255], [255, 255, 255, 0]];
+            let options = ResizeOptions {
+                filter: ResizeFilter::Box,
+                straight_alpha: true,
+                ..Default::default()
+            };
+
+            assert_eq!(
+                resize_pixels(&pixels, 2, 1, &options),
+                [[255, 128, 128, 128]]
+            );
+        }
+
+        #[test]
+        fn same_color_when_input_is_uniformly_semi_transparent() {
+            let pixels = [[100, 150, 200, 128]; 64];
+            let options = ResizeOptions::default();
+
+            let output = resize_pixels(&pixels, 8, 3, &options);
+
+            for pixel in output.iter() {
+                assert!(
+                    pixel
+                        .iter()
+                        .zip([100, 150, 200, 128].iter())
+                        .all(|(a, b)| (*a as i16 - *b as i16).abs() <= 1),
+                    "{:?}",
+                    pixel
+                );
+            }
+        }
+
+        #[test]
+        fn no_halo_on_hard_edge_when_transparent_part_is_white() {
+            // 左半分が不透明な赤、右半分が透明な白
+            let pixels: Vec<[u8; 4]> = (0..64)
+                .map(|i| match i % 8 < 4 {
+                    true => [255, 0, 0, 255],
+                    false => [255, 255, 255, 0],
+                })
+                .collect();
+            let options = ResizeOptions::default();
+
+            let output = resize_pixels(&pixels, 8, 3, &options);
+
+            assert!(output.iter().any(|p| p[3] > 0 && p[3] < 255));
+            for pixel in output.iter().filter(|p| p[3] > 0) {
+                assert!(
+                    pixel[0] >= 250 && pixel[1] == 0 && pixel[2] == 0,
+                    "{:?}",
+                    pixel
+                );
+            }
+        }
+
+        #[test]
+        fn no_halo_on_hard_edge_when_transparent_part_is_black() {
+            // 左半分が不透明な白、右半分が透明な黒
+            let pixels: Vec<[u8; 4]> = (0..64)
+                .map(|i| match i % 8 < 4 {
+                    true => [255, 255, 255, 255],
+                    false => [0, 0, 0, 0],
+                })
+                .collect();
+            let options = ResizeOptions::default();
+
+            let output = resize_pixels(&pixels, 8, 3, &options);
+
+            assert!(output.iter().any(|p| p[3] > 0 && p[3] < 255));
+            for pixel in output.iter().filter(|p| p[3] > 0) {
+                assert!(pixel[..3].iter().all(|v| *v >= 250), "{:?}", pixel);
+            }
+        }
+
+        #[test]
+        fn halo_on_hard_edge_when_alpha_is_straight() {
+            let pixels: Vec<[u8; 4]> = (0..64)
+                .map(|i| match i % 8 < 4 {
+                    true => [255, 255, 255, 255],
+                    false => [0, 0, 0, 0],
+                })
+                .collect();
+            let options = ResizeOptions {
+                straight_alpha: true,
+                ..Default::default()
+            };
+
+            let output = resize_pixels(&pixels, 8, 3, &options);
+
+            assert!(output
+                .iter()
+                .filter(|p| p[3] > 0)
+                .any(|p| p[..3].iter().any(|v| *v < 250)));
+        }
+
+        #[test]
+        fn no_halo_on_hard_edge_when_linear_light_is_enabled() {
+            let pixels: Vec<[u8; 4]> = (0..64)
+                .map(|i| match i % 8 < 4 {
+                    true => [255, 0, 0, 255],
+                    false => [255, 255, 255, 0],
+                })
+                .collect();
+            let options = ResizeOptions {
+                linear_light: true,
+                ..Default::default()
+            };
+
+            let output = resize_pixels(&pixels, 8, 3, &options);
+
+            for pixel in output.iter().filter(|p| p[3] > 0) {
+                assert!(
+                    pixel[0] >= 250 && pixel[1] == 0 && pixel[2] == 0,
+                    "{:?}",
+                    pixel
+                );
+            }
+        }
+    }
+
+    mod rotate_to_png {
+        use super::*;
+
+        use tempfile::tempdir;
+
+        #[test]
+        fn rotated_pixels_when_rotation_is_90() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.png");
+            let dist_path = out_dir.path().join("rotate.png");
+
+            rotate_to_png(&src_path, &dist_path, Rotation::Rotate90).unwrap();
+
+            let input = image::open(&src_path).unwrap().to_rgba8();
+            let output = image::open(&dist_path).unwrap().to_rgba8();
+            assert_eq!(image::imageops::rotate90(&input), output);
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod flip_to_png {
+        use super::*;
+
+        use tempfile::tempdir;
+
+        #[test]
+        fn flipped_pixels_when_flip_is_horizontal() {
+            let out_dir = tempdir().unwrap();
+
+            let src_path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.png");
+            let dist_path = out_dir.path().join("flip.png");
+
+            flip_to_png(&src_path, &dist_path, Flip::Horizontal).unwrap();
+
+            let input = image::open(&src_path).unwrap().to_rgba8();
+            let output = image::open(&dist_path).unwrap().to_rgba8();
+            assert_eq!(image::imageops::flip_horizontal(&input), output);
+
+            out_dir.close().unwrap();
+        }
+    }
+
+    mod output_size {
+        use super::*;
+
+        #[test]
+        fn none_when_both_width_and_height_are_minus() {
+            let width_command = -1;
+            let height_command = -1;
+            let input_width = NonZeroU32::new(100).unwrap();
+            let input_height = NonZeroU32::new(200).unwrap();
+
+            assert!(
+                output_size(width_command, height_command, input_width, input_height).is_none()
+            );
+        }
+
+        #[test]
+        fn original_value_when_width_and_height_are_0() {
+            let width_command = 0;
+            let height_command = 0;
+            let input_width = NonZeroU32::new(100).unwrap();
+            let input_height = NonZeroU32::new(200).unwrap();
+
+            let (width, height) =
+                output_size(width_command, height_command, input_width, input_height).unwrap();
+
+            assert_eq!(width, input_width);
+            assert_eq!(height, input_height);
+        }
+
+        #[test]
+        fn keep_aspect_ratio_when_one_of_width_and_height_is_minus() {
+            let width_command = -1;
+            let height_command = 100;
+            let input_width = NonZeroU32::new(100).unwrap();
+            let input_height = NonZeroU32::new(200).unwrap();
+
+            let (width, height) =
+                output_size(width_command, height_command, input_width, input_height).unwrap();
+
+            assert_eq!(width, NonZeroU32::new(50).unwrap());
+            assert_eq!(height, NonZeroU32::new(100).unwrap());
+        }
+
+        #[test]
+        fn target_values_when_width_and_henght_are_not_0_and_minis() {
+            let width_command = 200;
+            let height_command = 300;
             let input_width = NonZeroU32::new(100).unwrap();
             let input_height = NonZeroU32::new(200).unwrap();
 
@@ -297,4 +2667,460 @@ mod tests {
             assert_eq!(height, NonZeroU32::new(300).unwrap());
         }
     }
+
+    mod size_command {
+        use super::*;
+
+        #[test]
+        fn pixel_when_value_is_integer() {
+            assert_eq!("-1".parse::<SizeCommand>().unwrap(), SizeCommand::Pixel(-1));
+            assert_eq!(
+                "120".parse::<SizeCommand>().unwrap(),
+                SizeCommand::Pixel(120)
+            );
+        }
+
+        #[test]
+        fn percent_when_value_ends_with_percent_sign() {
+            assert_eq!(
+                "50%".parse::<SizeCommand>().unwrap(),
+                SizeCommand::Percent(50.0)
+            );
+            assert_eq!(
+                "12.5%".parse::<SizeCommand>().unwrap(),
+                SizeCommand::Percent(12.5)
+            );
+        }
+
+        #[test]
+        fn scale_when_value_starts_with_x() {
+            assert_eq!(
+                "x1.5".parse::<SizeCommand>().unwrap(),
+                SizeCommand::Scale(1.5)
+            );
+            assert_eq!(
+                "x2".parse::<SizeCommand>().unwrap(),
+                SizeCommand::Scale(2.0)
+            );
+        }
+
+        #[test]
+        fn failed_when_value_is_malformed() {
+            for case in [
+                "", "abc", "1.5", "%", "50%%", "x", "x-1", "-50%", "0%", "x0", "xinf",
+            ] {
+                assert!(
+                    matches!(
+                        case.parse::<SizeCommand>(),
+                        Err(ResizedPngError::InvalidSize)
+                    ),
+                    "{}",
+                    case
+                );
+            }
+        }
+
+        #[test]
+        fn checking_pixels_of_each_command() {
+            let input_size = NonZeroU32::new(200).unwrap();
+
+            assert_eq!(SizeCommand::Pixel(-1).to_pixels(input_size).unwrap(), -1);
+            assert_eq!(SizeCommand::Pixel(0).to_pixels(input_size).unwrap(), 0);
+            assert_eq!(
+                SizeCommand::Percent(50.0).to_pixels(input_size).unwrap(),
+                100
+            );
+            assert_eq!(SizeCommand::Scale(1.5).to_pixels(input_size).unwrap(), 300);
+            assert_eq!(SizeCommand::Percent(0.1).to_pixels(input_size).unwrap(), 1);
+        }
+
+        #[test]
+        fn failed_when_pixels_are_too_large() {
+            let input_size = NonZeroU32::new(200).unwrap();
+
+            for command in [
+                SizeCommand::Percent(100000000.0),
+                SizeCommand::Scale(1e9),
+                SizeCommand::Scale(f64::MAX),
+                SizeCommand::Pixel(MAX_OUTPUT_SIZE as i64 + 1),
+                SizeCommand::Pixel(4294967396),
+            ] {
+                assert!(
+                    matches!(
+                        command.to_pixels(input_size),
+                        Err(ResizedPngError::InvalidSize)
+                    ),
+                    "{:?}",
+                    command
+                );
+            }
+        }
+    }
+
+    mod output_layout {
+        use super::*;
+
+        fn size(v: u32) -> NonZeroU32 {
+            NonZeroU32::new(v).unwrap()
+        }
+
+        #[test]
+        fn whole_image_when_mode_is_stretch() {
+            let layout = output_layout(
+                size(50),
+                size(300),
+                size(100),
+                size(200),
+                ResizeMode::Stretch,
+                false,
+            );
+
+            assert_eq!(
+                layout,
+                OutputLayout {
+                    src: Rect {
+                        left: 0,
+                        top: 0,
+                        width: size(100),
+                        height: size(200)
+                    },
+                    resized_width: size(50),
+                    resized_height: size(300),
+                    canvas_width: size(50),
+                    canvas_height: size(300),
+                    offset_x: 0,
+                    offset_y: 0,
+                }
+            );
+        }
+
+        #[test]
+        fn inside_of_target_when_mode_is_fit() {
+            let layout = output_layout(
+                size(100),
+                size(100),
+                size(100),
+                size(200),
+                ResizeMode::Fit,
+                false,
+            );
+
+            assert_eq!(layout.src.width, size(100));
+            assert_eq!(layout.src.height, size(200));
+            assert_eq!(layout.resized_width, size(50));
+            assert_eq!(layout.resized_height, size(100));
+            assert_eq!(layout.canvas_width, size(50));
+            assert_eq!(layout.canvas_height, size(100));
+            assert_eq!((layout.offset_x, layout.offset_y), (0, 0));
+        }
+
+        #[test]
+        fn padded_to_target_when_mode_is_contain() {
+            let layout = output_layout(
+                size(100),
+                size(100),
+                size(100),
+                size(200),
+                ResizeMode::Contain,
+                false,
+            );
+
+            assert_eq!(layout.resized_width, size(50));
+            assert_eq!(layout.resized_height, size(100));
+            assert_eq!(layout.canvas_width, size(100));
+            assert_eq!(layout.canvas_height, size(100));
+            assert_eq!((layout.offset_x, layout.offset_y), (25, 0));
+        }
+
+        #[test]
+        fn center_cropped_when_mode_is_cover() {
+            let layout = output_layout(
+                size(100),
+                size(100),
+                size(100),
+                size(200),
+                ResizeMode::Cover,
+                false,
+            );
+
+            assert_eq!(
+                layout.src,
+                Rect {
+                    left: 0,
+                    top: 50,
+                    width: size(100),
+                    height: size(100)
+                }
+            );
+            assert_eq!(layout.resized_width, size(100));
+            assert_eq!(layout.resized_height, size(100));
+            assert_eq!(layout.canvas_width, size(100));
+            assert_eq!(layout.canvas_height, size(100));
+            assert_eq!((layout.offset_x, layout.offset_y), (0, 0));
+        }
+
+        #[test]
+        fn center_cropped_when_mode_is_cover_and_target_is_wide() {
+            let layout = output_layout(
+                size(40),
+                size(10),
+                size(100),
+                size(100),
+                ResizeMode::Cover,
+                false,
+            );
+
+            assert_eq!(
+                layout.src,
+                Rect {
+                    left: 0,
+                    top: 37,
+                    width: size(100),
+                    height: size(25)
+                }
+            );
+        }
+    }
+
+    mod output_layout_with_shrink_only {
+        use super::*;
+
+        fn size(v: u32) -> NonZeroU32 {
+            NonZeroU32::new(v).unwrap()
+        }
+
+        #[test]
+        fn input_size_when_mode_is_stretch_and_target_is_larger() {
+            let layout = output_layout(
+                size(300),
+                size(100),
+                size(100),
+                size(200),
+                ResizeMode::Stretch,
+                true,
+            );
+
+            assert_eq!(layout.resized_width, size(100));
+            assert_eq!(layout.resized_height, size(100));
+            assert_eq!(layout.canvas_width, size(100));
+            assert_eq!(layout.canvas_height, size(100));
+        }
+
+        #[test]
+        fn input_size_when_mode_is_fit_and_input_is_within_target() {
+            let layout = output_layout(
+                size(400),
+                size(400),
+                size(100),
+                size(200),
+                ResizeMode::Fit,
+                true,
+            );
+
+            assert_eq!(layout.src.width, layout.resized_width);
+            assert_eq!(layout.src.height, layout.resized_height);
+            assert_eq!(layout.canvas_width, size(100));
+            assert_eq!(layout.canvas_height, size(200));
+        }
+
+        #[test]
+        fn shrunk_size_when_mode_is_fit_and_input_is_larger_than_target() {
+            let layout = output_layout(
+                size(50),
+                size(400),
+                size(100),
+                size(200),
+                ResizeMode::Fit,
+                true,
+            );
+
+            assert_eq!(layout.resized_width, size(50));
+            assert_eq!(layout.resized_height, size(100));
+        }
+
+        #[test]
+        fn padded_input_when_mode_is_contain_and_input_is_within_target() {
+            let layout = output_layout(
+                size(200),
+                size(300),
+                size(100),
+                size(200),
+                ResizeMode::Contain,
+                true,
+            );
+
+            assert_eq!(layout.resized_width, size(100));
+            assert_eq!(layout.resized_height, size(200));
+            assert_eq!(layout.canvas_width, size(200));
+            assert_eq!(layout.canvas_height, size(300));
+            assert_eq!((layout.offset_x, layout.offset_y), (50, 50));
+        }
+
+        #[test]
+        fn cropped_input_when_mode_is_cover_and_target_needs_enlargement() {
+            let layout = output_layout(
+                size(300),
+                size(100),
+                size(100),
+                size(200),
+                ResizeMode::Cover,
+                true,
+            );
+
+            assert_eq!(
+                layout.src,
+                Rect {
+                    left: 0,
+                    top: 50,
+                    width: size(100),
+                    height: size(100)
+                }
+            );
+            assert_eq!(layout.resized_width, size(100));
+            assert_eq!(layout.resized_height, size(100));
+            assert_eq!(layout.canvas_width, size(100));
+            assert_eq!(layout.canvas_height, size(100));
+        }
+    }
+
+    mod rect {
+        use super::*;
+
+        fn size(v: u32) -> NonZeroU32 {
+            NonZeroU32::new(v).unwrap()
+        }
+
+        #[test]
+        fn success_when_value_is_valid() {
+            assert_eq!(
+                "1, 2,30,40".parse::<Rect>().unwrap(),
+                Rect {
+                    left: 1,
+                    top: 2,
+                    width: size(30),
+                    height: size(40)
+                }
+            );
+        }
+
+        #[test]
+        fn failed_when_value_is_malformed() {
+            for case in ["", "1,2,3", "1,2,3,4,5", "-1,2,3,4", "1,2,0,4", "a,b,c,d"] {
+                assert!(
+                    matches!(case.parse::<Rect>(), Err(ResizedPngError::InvalidRect)),
+                    "{}",
+                    case
+                );
+            }
+        }
+
+        #[test]
+        fn success_when_rect_is_inside_of_image() {
+            let rect = Rect::from_strs("10", "20", "90", "180").unwrap();
+
+            assert!(rect.check_inside(size(100), size(200)).is_ok());
+        }
+
+        #[test]
+        fn failed_when_rect_is_outside_of_image() {
+            let rect = Rect::from_strs("10", "20", "91", "180").unwrap();
+            assert!(matches!(
+                rect.check_inside(size(100), size(200)),
+                Err(ResizedPngError::InvalidRect)
+            ));
+
+            let rect = Rect::from_strs("0", "200", "1", "1").unwrap();
+            assert!(matches!(
+                rect.check_inside(size(100), size(200)),
+                Err(ResizedPngError::InvalidRect)
+            ));
+        }
+    }
+
+    mod apply_color_key {
+        use super::*;
+
+        use image::{Rgb, RgbImage};
+
+        #[test]
+        fn key_color_is_transparent() {
+            let mut image = RgbImage::from_pixel(3, 1, Rgb([0, 255, 0]));
+            image.put_pixel(1, 0, Rgb([0, 254, 0]));
+
+            let image = apply_color_key(DynamicImage::ImageRgb8(image)).to_rgba8();
+
+            assert_eq!(image.into_raw(), [0, 0, 0, 0, 0, 254, 0, 255, 0, 0, 0, 0]);
+        }
+
+        #[test]
+        fn key_color_is_compared_in_16bit_when_image_is_16bit() {
+            let mut image = ImageBuffer::from_pixel(3, 1, Rgb([0u16, 65535, 0]));
+            image.put_pixel(1, 0, Rgb([0, 65534, 0]));
+
+            let image = apply_color_key(DynamicImage::ImageRgb16(image));
+
+            assert_eq!(image.color(), image::ColorType::Rgba16);
+            assert_eq!(
+                image.to_rgba16().into_raw(),
+                [0, 0, 0, 0, 0, 65534, 0, 65535, 0, 0, 0, 0]
+            );
+        }
+    }
+
+    mod crop_image {
+        use super::*;
+
+        #[test]
+        fn pixels_inside_of_rect() {
+            let size = |v| NonZeroU32::new(v).unwrap();
+            let image =
+                fir::Image::from_vec_u8(size(3), size(3), (0..9).collect(), fir::PixelType::U8)
+                    .unwrap();
+            let rect = Rect {
+                left: 1,
+                top: 1,
+                width: size(2),
+                height: size(2),
+            };
+
+            let cropped = crop_image(&image, rect);
+
+            assert_eq!(cropped.width(), size(2));
+            assert_eq!(cropped.height(), size(2));
+            assert_eq!(cropped.buffer(), [4, 5, 7, 8]);
+        }
+    }
+
+    mod place_on_canvas {
+        use super::*;
+
+        #[test]
+        fn filled_with_background_outside_of_resized_image() {
+            let size = |v| NonZeroU32::new(v).unwrap();
+            let resized_image =
+                fir::Image::from_vec_u8(size(1), size(2), vec![9; 8], fir::PixelType::U8x4)
+                    .unwrap();
+            let layout = output_layout(
+                size(3),
+                size(2),
+                size(1),
+                size(2),
+                ResizeMode::Contain,
+                false,
+            );
+
+            let canvas = place_on_canvas(resized_image, &layout, [1, 2, 3, 4]);
+
+            assert_eq!(canvas.width(), size(3));
+            assert_eq!(canvas.height(), size(2));
+            assert_eq!(
+                canvas.buffer(),
+                [
+                    [1, 2, 3, 4, 9, 9, 9, 9, 1, 2, 3, 4],
+                    [1, 2, 3, 4, 9, 9, 9, 9, 1, 2, 3, 4]
+                ]
+                .concat()
+            );
+        }
+    }
 }
@@ -0,0 +1,691 @@
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+};
+
+use ab_glyph::{point, Font, FontVec, Glyph, PxScale, ScaleFont};
+use image::{DynamicImage, Rgba, RgbaImage};
+
+use crate::composite::{blend_pixel, BlendMode};
+use crate::error::ResizedPngError;
+use crate::options::parse_color;
+use crate::pna::merge_pna;
+use crate::resized_png::{decode_image, save_png, MAX_OUTPUT_SIZE};
+
+/// 文字の大きさ(ピクセル)の最大値
+pub(crate) const MAX_TEXT_SIZE: f32 = 1024.0;
+
+/// 縁取りの幅(ピクセル)の最大値
+const MAX_OUTLINE: u32 = 64;
+
+/// 文字を描く設定
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct TextOptions {
+    /// 文字の色(RGBA)
+    pub(crate) color: [u8; 4],
+    /// 縁取りの幅(ピクセル)。0のときは縁取りしない
+    pub(crate) outline: u32,
+    /// 縁取りの色(RGBA)
+    pub(crate) outline_color: [u8; 4],
+    /// この幅(ピクセル)を超える行を折り返す
+    pub(crate) wrap: Option<u32>,
+    /// 行の間隔(フォントの行の高さに対する倍率)
+    pub(crate) line_spacing: f32,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        TextOptions {
+            color: [0, 0, 0, 255],
+            outline: 0,
+            outline_color: [255, 255, 255, 255],
+            wrap: None,
+            line_spacing: 1.0,
+        }
+    }
+}
+
+impl TextOptions {
+    /// `名前=値`形式の引数から設定を読み取る関数。
+    pub(crate) fn from_args(args: &[String]) -> Result<TextOptions, ResizedPngError> {
+        let mut options = TextOptions::default();
+
+        for arg in args {
+            // 空の引数は指定なしとして扱う。
+            if arg.is_empty() {
+                continue;
+            }
+
+            let (name, value) = arg.split_once('=').ok_or(ResizedPngError::InvalidOption)?;
+
+            match name {
+                "Color" => {
+                    options.color = parse_color(value).ok_or(ResizedPngError::InvalidOption)?;
+                }
+                "Outline" => {
+                    options.outline = value
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|v| *v <= MAX_OUTLINE)
+                        .ok_or(ResizedPngError::InvalidOption)?;
+                }
+                "OutlineColor" => {
+                    options.outline_color =
+                        parse_color(value).ok_or(ResizedPngError::InvalidOption)?;
+                }
+                "Wrap" => {
+                    options.wrap = Some(
+                        value
+                            .parse::<u32>()
+                            .ok()
+                            .filter(|v| *v > 0)
+                            .ok_or(ResizedPngError::InvalidOption)?,
+                    );
+                }
+                "LineSpacing" => {
+                    options.line_spacing = value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|v| v.is_finite() && *v > 0.0)
+                        .ok_or(ResizedPngError::InvalidOption)?;
+                }
+                _ => return Err(ResizedPngError::InvalidOption),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// 文字の濃さ(0から1)の画像
+#[derive(Debug, PartialEq, Clone)]
+struct Coverage {
+    width: u32,
+    height: u32,
+    values: Vec<f32>,
+}
+
+impl Coverage {
+    fn new(width: u32, height: u32) -> Coverage {
+        Coverage {
+            width,
+            height,
+            values: vec![0.0; width as usize * height as usize],
+        }
+    }
+
+    fn get(&self, x: u32, y: u32) -> f32 {
+        self.values[(y * self.width + x) as usize]
+    }
+
+    fn set_max(&mut self, x: u32, y: u32, value: f32) {
+        let v = &mut self.values[(y * self.width + x) as usize];
+        *v = v.max(value);
+    }
+}
+
+/// 引数の`\n`を改行に、`\\`を`\`に置き換える関数。
+/// SAORIの引数は改行を含められないため、改行はこの形で受け取る。
+pub(crate) fn unescape_text(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => output.push('\n'),
+            Some('\\') => output.push('\\'),
+            Some(v) => {
+                output.push('\\');
+                output.push(v);
+            }
+            None => output.push('\\'),
+        }
+    }
+
+    output
+}
+
+/// 文字を描いてpngとして出力する関数。
+/// 下の画像がない場合は、文字の大きさに合わせた透明な画像に描き、位置を四辺の余白として扱う。
+pub(crate) fn draw_text_to_png(
+    base_path: Option<&PathBuf>,
+    dist_path: &Path,
+    text: &str,
+    font_path: &Path,
+    size: f32,
+    position: (i64, i64),
+    options: &TextOptions,
+) -> Result<(), ResizedPngError> {
+    let (x, y) = position;
+    let font = FontVec::try_from_vec(std::fs::read(font_path)?)
+        .map_err(|_| ResizedPngError::Unsupported)?;
+    let coverage = render_text(&font, size, text, options)?;
+
+    // 縁取りの分だけ、文字の画像は外側に広がっている。
+    let outline = options.outline as i64;
+    let (mut image, left, top) = match base_path {
+        Some(path) => {
+            let image = merge_pna(decode_image(path)?, path)?.to_rgba8();
+            (image, x - outline, y - outline)
+        }
+        None => {
+            let margin_x = u32::try_from(x.max(0)).map_err(|_| ResizedPngError::InvalidOption)?;
+            let margin_y = u32::try_from(y.max(0)).map_err(|_| ResizedPngError::InvalidOption)?;
+            let image = RgbaImage::new(
+                padded_size(coverage.width, margin_x)?.max(1),
+                padded_size(coverage.height, margin_y)?.max(1),
+            );
+            (image, margin_x as i64, margin_y as i64)
+        }
+    };
+
+    if options.outline > 0 {
+        let outline_coverage = dilate(&coverage, options.outline);
+        draw_coverage(
+            &mut image,
+            &outline_coverage,
+            left,
+            top,
+            options.outline_color,
+        );
+    }
+    draw_coverage(&mut image, &coverage, left, top, options.color);
+
+    save_png(DynamicImage::ImageRgba8(image), dist_path)
+}
+
+/// 文字を並べて、濃さの画像にする関数。
+/// 縁取りの幅だけ、四辺に余白をつける。
+fn render_text(
+    font: &FontVec,
+    size: f32,
+    text: &str,
+    options: &TextOptions,
+) -> Result<Coverage, ResizedPngError> {
+    let scaled_font = font.as_scaled(PxScale::from(size));
+    let line_height = (scaled_font.height() + scaled_font.line_gap()) * options.line_spacing;
+
+    let lines = layout_lines(&scaled_font, text, options.wrap);
+
+    let width = lines
+        .iter()
+        .map(|(_, width)| width.ceil() as u32)
+        .max()
+        .unwrap_or(0);
+    let height = match lines.len() {
+        0 => 0,
+        n => (line_height * (n - 1) as f32 + scaled_font.height()).ceil() as u32,
+    };
+
+    let padding = options.outline;
+    let mut coverage = Coverage::new(padded_size(width, padding)?, padded_size(height, padding)?);
+
+    for (i, (glyphs, _)) in lines.into_iter().enumerate() {
+        let baseline = padding as f32 + scaled_font.ascent() + line_height * i as f32;
+        for mut glyph in glyphs {
+            glyph.position.x += padding as f32;
+            glyph.position.y += baseline;
+
+            let Some(outlined) = font.outline_glyph(glyph) else {
+                continue;
+            };
+            let bounds = outlined.px_bounds();
+            outlined.draw(|gx, gy, c| {
+                let px = bounds.min.x as i64 + gx as i64;
+                let py = bounds.min.y as i64 + gy as i64;
+                if (0..coverage.width as i64).contains(&px)
+                    && (0..coverage.height as i64).contains(&py)
+                {
+                    coverage.set_max(px as u32, py as u32, c);
+                }
+            });
+        }
+    }
+
+    Ok(coverage)
+}
+
+/// 大きさに四辺の余白を足した大きさを返す関数。
+/// `MAX_OUTPUT_SIZE`を超える場合はエラーにする。
+fn padded_size(size: u32, padding: u32) -> Result<u32, ResizedPngError> {
+    padding
+        .checked_mul(2)
+        .and_then(|v| v.checked_add(size))
+        .filter(|v| *v <= MAX_OUTPUT_SIZE)
+        .ok_or(ResizedPngError::InvalidOption)
+}
+
+/// 文字を行ごとに並べる関数。
+/// 各行の文字(基準線を0とした位置)と、行の幅を返す。
+/// 折り返す幅があれば、超える手前の文字で折り返す。
+fn layout_lines<F: Font>(
+    scaled_font: &ab_glyph::PxScaleFont<F>,
+    text: &str,
+    wrap: Option<u32>,
+) -> Vec<(Vec<Glyph>, f32)> {
+    let mut lines = Vec::new();
+
+    for line in text.split('\n') {
+        let mut glyphs: Vec<Glyph> = Vec::new();
+        let mut caret = 0.0;
+        let mut previous = None;
+
+        for c in line.chars() {
+            let id = scaled_font.glyph_id(c);
+            if let Some(previous) = previous {
+                caret += scaled_font.kern(previous, id);
+            }
+            let advance = scaled_font.h_advance(id);
+
+            if let Some(wrap) = wrap {
+                if !glyphs.is_empty() && caret + advance > wrap as f32 {
+                    lines.push((std::mem::take(&mut glyphs), caret));
+                    caret = 0.0;
+                }
+            }
+
+            glyphs.push(id.with_scale_and_position(scaled_font.scale(), point(caret, 0.0)));
+            caret += advance;
+            previous = Some(id);
+        }
+
+        lines.push((glyphs, caret));
+    }
+
+    lines
+}
+
+/// 濃さの画像を、指定された幅だけ円形に太らせる関数。
+/// 円を横方向の線に分け、線の幅ごとに横方向の最大値を求めてから、縦にずらして重ねる。
+fn dilate(coverage: &Coverage, radius: u32) -> Coverage {
+    let mut output = Coverage::new(coverage.width, coverage.height);
+    let r = radius as i64;
+
+    // 縦方向のずれと、その位置での円の半分の幅
+    let lines: Vec<(i64, u32)> = (-r..=r)
+        .map(|dy| (dy, ((r * r - dy * dy) as f64).sqrt() as u32))
+        .collect();
+    let mut half_widths: Vec<u32> = lines.iter().map(|(_, v)| *v).collect();
+    half_widths.sort_unstable();
+    half_widths.dedup();
+
+    for half_width in half_widths {
+        let row_max = horizontal_max(coverage, half_width);
+        for (dy, _) in lines.iter().filter(|(_, v)| *v == half_width) {
+            for y in 0..coverage.height {
+                let src_y = y as i64 + dy;
+                if !(0..coverage.height as i64).contains(&src_y) {
+                    continue;
+                }
+                for x in 0..coverage.width {
+                    output.set_max(x, y, row_max.get(x, src_y as u32));
+                }
+            }
+        }
+    }
+
+    output
+}
+
+/// 各画素の値を、左右に指定された幅までの範囲の最大値にする関数。
+fn horizontal_max(coverage: &Coverage, half_width: u32) -> Coverage {
+    let mut output = Coverage::new(coverage.width, coverage.height);
+    let width = coverage.width as usize;
+    let half_width = half_width as usize;
+    if width == 0 {
+        return output;
+    }
+
+    // 範囲の中の最大値になりうる位置だけを、値の大きい順に残す。
+    let mut window: VecDeque<usize> = VecDeque::with_capacity(half_width * 2 + 1);
+    for (row, output_row) in coverage
+        .values
+        .chunks_exact(width)
+        .zip(output.values.chunks_exact_mut(width))
+    {
+        window.clear();
+        for right in 0..width + half_width {
+            if right < width {
+                while window.back().is_some_and(|v| row[*v] <= row[right]) {
+                    window.pop_back();
+                }
+                window.push_back(right);
+            }
+            let Some(x) = right.checked_sub(half_width) else {
+                continue;
+            };
+            while window.front().is_some_and(|v| v + half_width < x) {
+                window.pop_front();
+            }
+            output_row[x] = row[*window.front().expect("window includes x")];
+        }
+    }
+
+    output
+}
+
+/// 濃さの画像を、指定された色で画像に重ねる関数。
+fn draw_coverage(image: &mut RgbaImage, coverage: &Coverage, left: i64, top: i64, color: [u8; 4]) {
+    for y in 0..coverage.height {
+        for x in 0..coverage.width {
+            let value = coverage.get(x, y);
+            let px = left + x as i64;
+            let py = top + y as i64;
+            if value <= 0.0
+                || !(0..image.width() as i64).contains(&px)
+                || !(0..image.height() as i64).contains(&py)
+            {
+                continue;
+            }
+
+            let alpha = (color[3] as f32 * value.min(1.0)).round() as u8;
+            let pixel = image.get_pixel_mut(px as u32, py as u32);
+            *pixel = Rgba(blend_pixel(
+                pixel.0,
+                [color[0], color[1], color[2], alpha],
+                BlendMode::Normal,
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    /// テストに使うフォント。ASCIIと「さくら日本語」の文字だけを含む
+    fn test_font_path() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/font/NotoSansJP-Subset.otf")
+    }
+
+    mod text_options {
+        use super::*;
+
+        #[test]
+        fn success_when_args_are_valid() {
+            let args = vec![
+                String::from("Color=#FF0000"),
+                String::from(""),
+                String::from("Outline=2"),
+                String::from("OutlineColor=#00000080"),
+                String::from("Wrap=200"),
+                String::from("LineSpacing=1.5"),
+            ];
+
+            let options = TextOptions::from_args(&args).unwrap();
+
+            assert_eq!(
+                options,
+                TextOptions {
+                    color: [255, 0, 0, 255],
+                    outline: 2,
+                    outline_color: [0, 0, 0, 128],
+                    wrap: Some(200),
+                    line_spacing: 1.5,
+                }
+            );
+        }
+
+        #[test]
+        fn failed_when_args_are_invalid() {
+            for arg in [
+                "Color=red",
+                "Outline=-1",
+                "Outline=65",
+                "Wrap=0",
+                "LineSpacing=0",
+                "Size=12",
+            ] {
+                let args = vec![String::from(arg)];
+
+                assert!(matches!(
+                    TextOptions::from_args(&args),
+                    Err(ResizedPngError::InvalidOption)
+                ));
+            }
+        }
+    }
+
+    mod unescape_text {
+        use super::*;
+
+        #[test]
+        fn newline_when_text_has_escape() {
+            assert_eq!(unescape_text("一行目\\n二行目"), "一行目\n二行目");
+            assert_eq!(unescape_text("C:\\\\path"), "C:\\path");
+            assert_eq!(unescape_text("a\\tb\\"), "a\\tb\\");
+        }
+    }
+
+    mod padded_size {
+        use super::*;
+
+        #[test]
+        fn size_with_padding_when_size_is_small() {
+            assert_eq!(padded_size(100, 4).unwrap(), 108);
+        }
+
+        #[test]
+        fn failed_when_size_is_too_large() {
+            for (size, padding) in [(MAX_OUTPUT_SIZE, 1), (0, u32::MAX), (u32::MAX, 0)] {
+                assert!(matches!(
+                    padded_size(size, padding),
+                    Err(ResizedPngError::InvalidOption)
+                ));
+            }
+        }
+    }
+
+    mod dilate {
+        use super::*;
+
+        #[test]
+        fn circle_when_single_pixel_is_dilated() {
+            let mut coverage = Coverage::new(5, 5);
+            coverage.set_max(2, 2, 1.0);
+
+            let output = dilate(&coverage, 2);
+
+            assert_eq!(output.get(0, 2), 1.0);
+            assert_eq!(output.get(1, 1), 1.0);
+            assert_eq!(output.get(0, 0), 0.0);
+        }
+
+        #[test]
+        fn largest_value_in_circle_when_values_are_mixed() {
+            let mut coverage = Coverage::new(9, 9);
+            coverage.set_max(1, 4, 0.5);
+            coverage.set_max(4, 4, 0.25);
+            coverage.set_max(7, 7, 1.0);
+
+            let output = dilate(&coverage, 3);
+
+            assert_eq!(output.get(4, 4), 0.5);
+            assert_eq!(output.get(4, 1), 0.25);
+            assert_eq!(output.get(5, 5), 1.0);
+            assert_eq!(output.get(0, 0), 0.0);
+            assert_eq!(output.get(8, 0), 0.0);
+        }
+    }
+
+    mod draw_text_to_png {
+        use super::*;
+
+        #[test]
+        fn text_size_image_when_base_is_not_specified() {
+            let font_path = test_font_path();
+            let out_dir = tempdir().unwrap();
+            let dist_path = out_dir.path().join("output.png");
+            let options = TextOptions {
+                color: [255, 0, 0, 255],
+                ..Default::default()
+            };
+
+            draw_text_to_png(None, &dist_path, "Ag", &font_path, 32.0, (4, 2), &options).unwrap();
+
+            let output = image::open(&dist_path).unwrap().to_rgba8();
+            assert!(output.width() > 8 && output.height() > 4);
+            // 余白は透明のまま。
+            assert_eq!(output.get_pixel(0, 0).0[3], 0);
+            assert!(output.pixels().any(|v| v.0 == [255, 0, 0, 255]));
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn taller_image_when_text_is_wrapped() {
+            let font_path = test_font_path();
+            let out_dir = tempdir().unwrap();
+            let single_path = out_dir.path().join("single.png");
+            let wrapped_path = out_dir.path().join("wrapped.png");
+            let options = TextOptions {
+                wrap: Some(25),
+                ..Default::default()
+            };
+
+            draw_text_to_png(
+                None,
+                &single_path,
+                "abcdefgh",
+                &font_path,
+                20.0,
+                (0, 0),
+                &TextOptions::default(),
+            )
+            .unwrap();
+            draw_text_to_png(
+                None,
+                &wrapped_path,
+                "abcdefgh",
+                &font_path,
+                20.0,
+                (0, 0),
+                &options,
+            )
+            .unwrap();
+
+            let single = image::open(&single_path).unwrap();
+            let wrapped = image::open(&wrapped_path).unwrap();
+            assert!(wrapped.width() <= 25);
+            assert!(wrapped.height() > single.height() * 2);
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn outline_color_around_text_when_outline_is_specified() {
+            let font_path = test_font_path();
+            let out_dir = tempdir().unwrap();
+            let base_path = out_dir.path().join("base.png");
+            RgbaImage::from_pixel(80, 60, Rgba([0, 0, 255, 255]))
+                .save(&base_path)
+                .unwrap();
+            let dist_path = out_dir.path().join("output.png");
+            let options = TextOptions {
+                color: [0, 0, 0, 255],
+                outline: 3,
+                outline_color: [255, 255, 255, 255],
+                ..Default::default()
+            };
+
+            draw_text_to_png(
+                Some(&base_path),
+                &dist_path,
+                "I",
+                &font_path,
+                40.0,
+                (20, 5),
+                &options,
+            )
+            .unwrap();
+
+            let output = image::open(&dist_path).unwrap().to_rgba8();
+            assert_eq!(output.dimensions(), (80, 60));
+            assert!(output.pixels().any(|v| v.0 == [255, 255, 255, 255]));
+            assert!(output.pixels().any(|v| v.0 == [0, 0, 0, 255]));
+            assert_eq!(output.get_pixel(79, 59).0, [0, 0, 255, 255]);
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn glyphs_drawn_when_text_is_japanese() {
+            let out_dir = tempdir().unwrap();
+            let dist_path = out_dir.path().join("output.png");
+            let options = TextOptions {
+                color: [255, 0, 0, 255],
+                ..Default::default()
+            };
+
+            draw_text_to_png(
+                None,
+                &dist_path,
+                "さくら\n日本語",
+                &test_font_path(),
+                24.0,
+                (0, 0),
+                &options,
+            )
+            .unwrap();
+
+            let output = image::open(&dist_path).unwrap().to_rgba8();
+            let (width, height) = output.dimensions();
+            assert!(width > height && height >= 24 * 2);
+            // 3文字ずつ2行に並び、どの文字の範囲にも描かれた画素がある。
+            for (column, row) in (0..2).flat_map(|row| (0..3).map(move |column| (column, row))) {
+                let xs = width * column / 3..width * (column + 1) / 3;
+                let ys = height * row / 2..height * (row + 1) / 2;
+                assert!(
+                    xs.clone()
+                        .any(|x| ys.clone().any(|y| output.get_pixel(x, y).0[3] > 0)),
+                    "{}, {}",
+                    column,
+                    row
+                );
+            }
+            assert!(output.pixels().any(|v| v.0 == [255, 0, 0, 255]));
+
+            out_dir.close().unwrap();
+        }
+
+        #[test]
+        fn glyphs_of_each_character_when_font_has_japanese() {
+            let font = FontVec::try_from_vec(std::fs::read(test_font_path()).unwrap()).unwrap();
+
+            for c in "さくら日本語".chars() {
+                let glyph = font.glyph_id(c).with_scale(24.0);
+                let outlined = font.outline_glyph(glyph);
+
+                assert!(outlined.is_some(), "{}", c);
+            }
+        }
+
+        #[test]
+        fn failed_when_font_is_invalid() {
+            let out_dir = tempdir().unwrap();
+            let font_path =
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_target/image/sample.png");
+            let dist_path = out_dir.path().join("output.png");
+
+            let result = draw_text_to_png(
+                None,
+                &dist_path,
+                "a",
+                &font_path,
+                12.0,
+                (0, 0),
+                &TextOptions::default(),
+            );
+
+            assert!(matches!(result, Err(ResizedPngError::Unsupported)));
+
+            out_dir.close().unwrap();
+        }
+    }
+}